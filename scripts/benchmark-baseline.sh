@@ -0,0 +1,32 @@
+#!/bin/sh
+# Benchmarks the current tree against BASELINE, a commit from before the
+# profiles shared their web context and scripts (8492f7d), which has no
+# benchmark of its own. BASELINE is checked out in a temporary worktree,
+# benchmark-baseline.patch adds the same benchmark to it, and both are
+# built in release mode and run through benchmark-tabs.sh. Results are
+# appended to bench_output.txt, labelled "before" and "after".
+#
+#     scripts/benchmark-baseline.sh 8492f7d [N]
+#
+# Environment: RUNS as for benchmark-tabs.sh.
+
+set -eu
+
+if [ $# -lt 1 ]; then
+    echo "usage: $0 BASELINE [N]" >&2
+    exit 1
+fi
+baseline=$1
+count=${2:-100}
+scripts=$(cd "$(dirname "$0")" && pwd)
+worktree=$(mktemp -d)
+
+trap 'git worktree remove --force "$worktree"' EXIT
+git worktree add --detach "$worktree" "$baseline"
+git -C "$worktree" apply "$scripts/benchmark-baseline.patch"
+
+(cd "$worktree" && cargo build --release)
+cargo build --release
+
+TILDE="$worktree/target/release/browser" LABEL=before "$scripts/benchmark-tabs.sh" "$count"
+LABEL=after "$scripts/benchmark-tabs.sh" "$count"
@@ -0,0 +1,42 @@
+#!/bin/sh
+# Times opening N small local pages (100 by default) in background tabs, in
+# a fresh data directory so no session or settings get in the way. The
+# benchmark opens its own window without start pages, so nothing is loaded
+# from the network, and a page that doesn't load within 30 seconds is
+# counted as timed out. Each run prints how long creating the tabs took and
+# how long until every page had loaded; the same lines are appended to
+# bench_output.txt.
+#
+# Compare two builds by running it against each:
+#     TILDE=path/to/old/browser scripts/benchmark-tabs.sh
+#     scripts/benchmark-tabs.sh
+# scripts/benchmark-baseline.sh builds the old one.
+#
+# Environment: TILDE is the binary (target/release/browser by default),
+# RUNS the number of runs (5 by default), LABEL a prefix for the lines.
+
+set -eu
+
+count=${1:-100}
+runs=${RUNS:-5}
+bin=${TILDE:-target/release/browser}
+label=${LABEL:+$LABEL }
+
+if [ ! -x "$bin" ]; then
+    echo "$bin not found, build it with: cargo build --release" >&2
+    exit 1
+fi
+
+run=1
+while [ "$run" -le "$runs" ]; do
+    home=$(mktemp -d)
+    XDG_DATA_HOME="$home/data" \
+        XDG_CONFIG_HOME="$home/config" \
+        XDG_CACHE_HOME="$home/cache" \
+        TILDE_BENCHMARK_TABS="$count" \
+        "$bin" 2>/dev/null |
+        grep '^benchmark:' |
+        sed "s/^/${label}run $run: /" | tee -a bench_output.txt
+    rm -rf "$home"
+    run=$((run + 1))
+done
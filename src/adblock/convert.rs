@@ -0,0 +1,582 @@
+//! Conversion of Adblock Plus / EasyList filters into WebKit content-blocker
+//! JSON. Anything WebKit cannot express is skipped rather than guessed.
+
+use super::cosmetic::{self, Hiding};
+use crate::scripts::js_string;
+
+/// Characters that may follow a domain or path component. WebKit's regular
+/// expressions have no alternation, so `^` cannot also mean end of address.
+const SEPARATOR: &str = "[^a-zA-Z0-9_.%-]";
+
+// Matches the scheme and any subdomains before a `||domain` anchor
+const DOMAIN_ANCHOR: &str = "^[^:]+://+([^:/]+\\.)?";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Conversion {
+    /// A JSON array of WebKit content-blocker rules.
+    pub json: String,
+    pub converted: usize,
+    pub skipped: usize,
+}
+
+#[derive(Debug, PartialEq)]
+enum Action {
+    Block,
+    IgnorePreviousRules,
+    CssDisplayNone(String),
+}
+
+#[derive(Debug, PartialEq)]
+struct Rule {
+    url_filter: String,
+    case_sensitive: bool,
+    resource_types: Vec<&'static str>,
+    load_type: Option<&'static str>,
+    load_context: Option<&'static str>,
+    if_domains: Vec<String>,
+    unless_domains: Vec<String>,
+    action: Action,
+    /// Not undone by exceptions.
+    important: bool,
+}
+
+impl Rule {
+    /// A rule for every address, narrowed by domain only.
+    fn for_domains(if_domains: Vec<String>, unless_domains: Vec<String>, action: Action) -> Self {
+        Rule {
+            url_filter: ".*".into(),
+            case_sensitive: false,
+            resource_types: Vec::new(),
+            load_type: None,
+            load_context: None,
+            if_domains,
+            unless_domains,
+            action,
+            important: false,
+        }
+    }
+}
+
+/// Converts every line of `list`. `disabled_sites` get a final rule that
+/// lifts all blocking on them.
+pub fn convert(list: &str, disabled_sites: &[String]) -> Conversion {
+    let mut blocks = Vec::new();
+    let mut important = Vec::new();
+    let mut exceptions = Vec::new();
+    let mut hidings = Vec::new();
+    let mut hiding_exceptions = Vec::new();
+    let mut skipped = 0;
+
+    for line in list.lines().map(str::trim) {
+        if is_ignored(line) {
+            continue;
+        }
+
+        if let Some(hiding) = cosmetic::parse(line) {
+            match hiding {
+                Some((true, hiding)) => hiding_exceptions.push(hiding),
+                Some((false, hiding)) => hidings.push(hiding),
+                None => skipped += 1,
+            }
+            continue;
+        }
+
+        match parse_rule(line) {
+            Some(rule) if rule.action == Action::IgnorePreviousRules => exceptions.push(rule),
+            Some(rule) if rule.important => important.push(rule),
+            Some(rule) => blocks.push(rule),
+            None => skipped += 1,
+        }
+    }
+    let converted =
+        blocks.len() + important.len() + exceptions.len() + hidings.len() + hiding_exceptions.len();
+
+    let hidings = cosmetic::apply_exceptions(hidings, &hiding_exceptions)
+        .into_iter()
+        .map(hiding_rule);
+
+    // Exceptions only undo rules that come before them, so `$important`
+    // blocks follow them
+    let mut json: Vec<String> = blocks
+        .into_iter()
+        .chain(hidings)
+        .chain(exceptions)
+        .chain(important)
+        .map(|rule| rule.to_json())
+        .collect();
+    json.extend(disabled_sites.iter().map(|site| {
+        Rule::for_domains(vec![site.clone()], Vec::new(), Action::IgnorePreviousRules).to_json()
+    }));
+
+    Conversion {
+        json: format!("[{}]", json.join(",")),
+        converted,
+        skipped,
+    }
+}
+
+/// Comments, headers and blank lines.
+fn is_ignored(line: &str) -> bool {
+    line.is_empty() || line.starts_with('!') || line.starts_with('[')
+}
+
+fn hiding_rule(hiding: Hiding) -> Rule {
+    let mut unless_domains = hiding.excluded;
+    if !hiding.domains.is_empty() {
+        unless_domains.clear();
+    }
+
+    Rule::for_domains(
+        hiding.domains,
+        unless_domains,
+        Action::CssDisplayNone(hiding.selector),
+    )
+}
+
+fn parse_rule(line: &str) -> Option<Rule> {
+    let (action, line) = match line.strip_prefix("@@") {
+        Some(rest) => (Action::IgnorePreviousRules, rest),
+        None => (Action::Block, line),
+    };
+
+    // Regular expression rules would need translating into WebKit's subset
+    if line.starts_with('/') && line.len() > 1 && line.ends_with('/') {
+        return None;
+    }
+    if !line.is_ascii() {
+        return None;
+    }
+
+    let (pattern, options) = match line.rsplit_once('$') {
+        Some((pattern, options)) if !options.contains('/') => (pattern, Some(options)),
+        _ => (line, None),
+    };
+
+    let mut rule = Rule {
+        url_filter: url_filter(pattern),
+        case_sensitive: false,
+        resource_types: Vec::new(),
+        load_type: None,
+        load_context: None,
+        if_domains: Vec::new(),
+        unless_domains: Vec::new(),
+        action,
+        important: false,
+    };
+
+    if let Some(options) = options {
+        apply_options(&mut rule, options)?;
+    }
+
+    Some(rule)
+}
+
+fn apply_options(rule: &mut Rule, options: &str) -> Option<()> {
+    let mut included = Vec::new();
+    let mut excluded = Vec::new();
+    let mut subdocument = false;
+
+    for option in options.split(',').map(str::trim) {
+        let (negated, name) = match option.strip_prefix('~') {
+            Some(name) => (true, name),
+            None => (false, option),
+        };
+
+        // WebKit's document type covers top-level pages too; frames are
+        // told apart by a load context below
+        if name == "subdocument" {
+            if negated {
+                excluded.push("document");
+            } else {
+                subdocument = true;
+            }
+            continue;
+        }
+
+        if let Some(resource_type) = resource_type(name) {
+            if negated {
+                excluded.push(resource_type);
+            } else {
+                included.push(resource_type);
+            }
+            continue;
+        }
+
+        match name {
+            "third-party" => {
+                rule.load_type = Some(if negated {
+                    "first-party"
+                } else {
+                    "third-party"
+                })
+            }
+            "match-case" => rule.case_sensitive = true,
+            // Only blocks can outrank exceptions; an important exception
+            // is just an exception
+            "important" => rule.important = rule.action == Action::Block,
+            // `@@||site^$document` turns blocking off for a whole site
+            "document" if rule.action == Action::IgnorePreviousRules && !negated => {
+                rule.if_domains.push(filter_domain(&rule.url_filter)?);
+                rule.url_filter = ".*".into();
+            }
+            _ if name.starts_with("domain=") => {
+                for domain in name["domain=".len()..].split('|') {
+                    match domain.strip_prefix('~') {
+                        Some(domain) => rule.unless_domains.push(domain.to_string()),
+                        None => rule.if_domains.push(domain.to_string()),
+                    }
+                }
+            }
+            _ => return None,
+        }
+    }
+
+    // The load context applies to the whole rule, so frames can't share
+    // it with other types
+    if subdocument {
+        if !included.is_empty() {
+            return None;
+        }
+        included.push("document");
+        rule.load_context = Some("child-frame");
+    }
+
+    rule.resource_types = if !included.is_empty() {
+        included
+    } else if !excluded.is_empty() {
+        RESOURCE_TYPES
+            .iter()
+            .copied()
+            .filter(|t| !excluded.contains(t))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    rule.resource_types.sort_unstable();
+    rule.resource_types.dedup();
+
+    // WebKit takes either list, not both; the exclusions of a rule that
+    // names domains are usually subdomains of them anyway
+    if !rule.if_domains.is_empty() {
+        rule.unless_domains.clear();
+    }
+
+    Some(())
+}
+
+const RESOURCE_TYPES: &[&str] = &[
+    "document",
+    "image",
+    "style-sheet",
+    "script",
+    "font",
+    "raw",
+    "svg-document",
+    "media",
+    "popup",
+    "ping",
+    "websocket",
+];
+
+fn resource_type(option: &str) -> Option<&'static str> {
+    Some(match option {
+        "script" => "script",
+        "image" => "image",
+        "stylesheet" => "style-sheet",
+        "font" => "font",
+        "media" => "media",
+        "xmlhttprequest" | "other" => "raw",
+        "popup" => "popup",
+        "ping" => "ping",
+        "websocket" => "websocket",
+        _ => return None,
+    })
+}
+
+/// Translates an ABP address pattern into a WebKit url-filter.
+fn url_filter(pattern: &str) -> String {
+    let (mut filter, rest) = if let Some(rest) = pattern.strip_prefix("||") {
+        (DOMAIN_ANCHOR.to_string(), rest)
+    } else if let Some(rest) = pattern.strip_prefix('|') {
+        ("^".to_string(), rest)
+    } else {
+        (String::new(), pattern)
+    };
+
+    let (rest, anchored_end) = match rest.strip_suffix('|') {
+        Some(rest) => (rest, true),
+        None => (rest, false),
+    };
+
+    for c in rest.chars() {
+        match c {
+            '*' => filter.push_str(".*"),
+            '^' => filter.push_str(SEPARATOR),
+            '.' | '+' | '?' | '(' | ')' | '[' | ']' | '{' | '}' | '\\' | '$' | '|' => {
+                filter.push('\\');
+                filter.push(c);
+            }
+            c => filter.push(c),
+        }
+    }
+
+    if anchored_end {
+        filter.push('$');
+    }
+    if filter.is_empty() {
+        filter.push_str(".*");
+    }
+    filter
+}
+
+/// The domain of a `||domain^` filter, for `$document` exceptions.
+fn filter_domain(filter: &str) -> Option<String> {
+    let rest = filter.strip_prefix(DOMAIN_ANCHOR)?;
+    let domain = rest.strip_suffix(SEPARATOR).unwrap_or(rest);
+    let domain = domain.replace("\\.", ".");
+
+    (!domain.is_empty() && !domain.contains(['\\', '*', '[', '/'])).then_some(domain)
+}
+
+impl Rule {
+    fn to_json(&self) -> String {
+        let mut trigger = vec![format!("\"url-filter\":{}", js_string(&self.url_filter))];
+
+        if self.case_sensitive {
+            trigger.push("\"url-filter-is-case-sensitive\":true".into());
+        }
+        if !self.resource_types.is_empty() {
+            trigger.push(format!(
+                "\"resource-type\":{}",
+                json_list(self.resource_types.iter().copied())
+            ));
+        }
+        if let Some(load_type) = self.load_type {
+            trigger.push(format!("\"load-type\":{}", json_list([load_type])));
+        }
+        if let Some(load_context) = self.load_context {
+            trigger.push(format!("\"load-context\":{}", json_list([load_context])));
+        }
+        // A leading `*` makes WebKit match subdomains too, as ABP does
+        if !self.if_domains.is_empty() {
+            trigger.push(format!(
+                "\"if-domain\":{}",
+                json_list(self.if_domains.iter().map(|d| format!("*{}", d)))
+            ));
+        }
+        if !self.unless_domains.is_empty() {
+            trigger.push(format!(
+                "\"unless-domain\":{}",
+                json_list(self.unless_domains.iter().map(|d| format!("*{}", d)))
+            ));
+        }
+
+        let action = match &self.action {
+            Action::Block => r#"{"type":"block"}"#.to_string(),
+            Action::IgnorePreviousRules => r#"{"type":"ignore-previous-rules"}"#.to_string(),
+            Action::CssDisplayNone(selector) => format!(
+                r#"{{"type":"css-display-none","selector":{}}}"#,
+                js_string(selector)
+            ),
+        };
+
+        format!(
+            "{{\"trigger\":{{{}}},\"action\":{}}}",
+            trigger.join(","),
+            action
+        )
+    }
+}
+
+fn json_list<S: AsRef<str>>(items: impl IntoIterator<Item = S>) -> String {
+    let items: Vec<String> = items.into_iter().map(|s| js_string(s.as_ref())).collect();
+    format!("[{}]", items.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASIC: &str = include_str!("../../tests/fixtures/adblock/basic.txt");
+    const OPTIONS: &str = include_str!("../../tests/fixtures/adblock/options.txt");
+
+    fn rule(line: &str) -> String {
+        let conversion = convert(line, &[]);
+        assert_eq!(conversion.converted, 1, "{} was not converted", line);
+        conversion.json[1..conversion.json.len() - 1].to_string()
+    }
+
+    #[test]
+    fn basic_list_counts() {
+        let conversion = convert(BASIC, &[]);
+        // Four network rules, two hiding rules and a hiding exception
+        assert_eq!(conversion.converted, 7);
+        // The regex, non-ASCII and $csp rules
+        assert_eq!(conversion.skipped, 3);
+    }
+
+    #[test]
+    fn options_list_counts() {
+        let conversion = convert(OPTIONS, &[]);
+        assert_eq!(conversion.converted, 11);
+        assert_eq!(conversion.skipped, 0);
+    }
+
+    #[test]
+    fn domain_anchor() {
+        assert_eq!(
+            rule("||ads.example.com^"),
+            r#"{"trigger":{"url-filter":"^[^:]+://+([^:/]+\\.)?ads\\.example\\.com[^a-zA-Z0-9_.%-]"},"action":{"type":"block"}}"#
+        );
+    }
+
+    #[test]
+    fn start_and_end_anchors() {
+        assert_eq!(
+            rule("|https://tracker.example.net/pixel.gif|"),
+            r#"{"trigger":{"url-filter":"^https://tracker\\.example\\.net/pixel\\.gif$"},"action":{"type":"block"}}"#
+        );
+    }
+
+    #[test]
+    fn wildcards_and_special_characters() {
+        assert_eq!(
+            rule("/banner/*/ad_"),
+            r#"{"trigger":{"url-filter":"/banner/.*/ad_"},"action":{"type":"block"}}"#
+        );
+        assert_eq!(
+            rule("&ad_type=?x+(1)"),
+            r#"{"trigger":{"url-filter":"&ad_type=\\?x\\+\\(1\\)"},"action":{"type":"block"}}"#
+        );
+    }
+
+    #[test]
+    fn element_hiding() {
+        assert_eq!(
+            rule("example.org##.sponsored"),
+            r#"{"trigger":{"url-filter":".*","if-domain":["*example.org"]},"action":{"type":"css-display-none","selector":".sponsored"}}"#
+        );
+
+        let conversion = convert("##.ad\nexample.org#@#.ad", &[]);
+        assert_eq!(
+            conversion.json,
+            r#"[{"trigger":{"url-filter":".*","unless-domain":["*example.org"]},"action":{"type":"css-display-none","selector":".ad"}}]"#
+        );
+    }
+
+    #[test]
+    fn hiding_comes_before_exceptions() {
+        let conversion = convert(
+            "@@||trusted.example^$document\n##.ad\n||ads.example.com^",
+            &[],
+        );
+        let block = conversion.json.find(r#""block""#).unwrap();
+        let hide = conversion.json.find("css-display-none").unwrap();
+        let ignore = conversion.json.find("ignore-previous-rules").unwrap();
+        assert!(block < hide && hide < ignore);
+    }
+
+    #[test]
+    fn resource_types() {
+        assert!(
+            rule("||cdn.example.com^$script,image")
+                .contains(r#""resource-type":["image","script"]"#)
+        );
+        assert!(rule("||a.example^$xmlhttprequest,other").contains(r#""resource-type":["raw"]"#));
+
+        let negated = rule("||fonts.example.com^$~font");
+        assert!(!negated.contains(r#""font""#));
+        assert!(negated.contains(r#""script""#));
+    }
+
+    #[test]
+    fn subdocuments_only_in_frames() {
+        assert_eq!(
+            rule("||frames.example^$subdocument"),
+            r#"{"trigger":{"url-filter":"^[^:]+://+([^:/]+\\.)?frames\\.example[^a-zA-Z0-9_.%-]","resource-type":["document"],"load-context":["child-frame"]},"action":{"type":"block"}}"#
+        );
+        assert!(!rule("||a.example^$~subdocument").contains(r#""document""#));
+
+        let mixed = convert("||a.example^$script,subdocument", &[]);
+        assert_eq!((mixed.converted, mixed.skipped), (0, 1));
+    }
+
+    #[test]
+    fn important_blocks_come_after_exceptions() {
+        let conversion = convert(
+            "||ads.example.com^$important\n@@||ads.example.com^",
+            &["news.example".to_string()],
+        );
+        let block = conversion.json.find(r#""block""#).unwrap();
+        let exception = conversion.json.find("ignore-previous-rules").unwrap();
+        let site = conversion.json.rfind("ignore-previous-rules").unwrap();
+        assert!(exception < block && block < site);
+    }
+
+    #[test]
+    fn party() {
+        assert!(rule("||a.example^$third-party").contains(r#""load-type":["third-party"]"#));
+        assert!(rule("||a.example^$~third-party").contains(r#""load-type":["first-party"]"#));
+    }
+
+    #[test]
+    fn domains() {
+        let both = rule("||w.example^$domain=news.example|~sports.news.example");
+        assert!(both.contains(r#""if-domain":["*news.example"]"#));
+        assert!(!both.contains("unless-domain"));
+
+        let unless = rule("||b.example^$domain=~shop.example");
+        assert!(unless.contains(r#""unless-domain":["*shop.example"]"#));
+    }
+
+    #[test]
+    fn match_case() {
+        assert_eq!(
+            rule("/Ads/Banner.$match-case"),
+            r#"{"trigger":{"url-filter":"/Ads/Banner\\.","url-filter-is-case-sensitive":true},"action":{"type":"block"}}"#
+        );
+    }
+
+    #[test]
+    fn exceptions_come_after_blocks() {
+        let conversion = convert("@@||ads.example.com/allowed/\n||ads.example.com^", &[]);
+        let block = conversion.json.find(r#""block""#).unwrap();
+        let ignore = conversion.json.find("ignore-previous-rules").unwrap();
+        assert!(block < ignore);
+    }
+
+    #[test]
+    fn document_exception_allows_whole_site() {
+        assert_eq!(
+            rule("@@||trusted.example^$document"),
+            r#"{"trigger":{"url-filter":".*","if-domain":["*trusted.example"]},"action":{"type":"ignore-previous-rules"}}"#
+        );
+    }
+
+    #[test]
+    fn unsupported_rules_are_skipped() {
+        for line in [
+            "/^https?:\\/\\/ads\\./",
+            "||ünïcode.example^",
+            "||a.example^$csp=script-src 'none'",
+            "||a.example^$redirect=noop.js",
+            "||a.example^$document",
+        ] {
+            let conversion = convert(line, &[]);
+            assert_eq!(
+                (conversion.converted, conversion.skipped),
+                (0, 1),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn disabled_sites_come_last() {
+        let conversion = convert("||ads.example.com^", &["news.example".to_string()]);
+        assert!(conversion.json.ends_with(
+            r#"{"trigger":{"url-filter":".*","if-domain":["*news.example"]},"action":{"type":"ignore-previous-rules"}}]"#
+        ));
+    }
+}
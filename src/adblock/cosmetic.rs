@@ -0,0 +1,159 @@
+//! Element hiding rules (`domains##selector`), which become
+//! `css-display-none` content-blocker rules. `#@#` exceptions are resolved
+//! here, since WebKit has no way to unhide a single selector.
+
+#[derive(Debug, PartialEq)]
+pub struct Hiding {
+    pub selector: String,
+    pub domains: Vec<String>,
+    pub excluded: Vec<String>,
+}
+
+// Procedural and scriptlet syntax from other blockers, which CSS can't do
+const UNSUPPORTED: &[&str] = &[
+    ":-abp-",
+    ":has-text(",
+    ":xpath(",
+    ":matches-css",
+    ":matches-path(",
+    ":min-text-length(",
+    ":upward(",
+    ":remove(",
+    ":style(",
+    ":watch-attr(",
+    ":others(",
+];
+
+/// Parses an element hiding rule or exception, returning whether it is an
+/// exception. `None` if `line` is not one; `Some(None)` if it is one that
+/// cannot be expressed.
+pub fn parse(line: &str) -> Option<Option<(bool, Hiding)>> {
+    if ["#?#", "#$#", "#@?#", "#@$#"]
+        .iter()
+        .any(|marker| line.contains(marker))
+    {
+        return Some(None);
+    }
+
+    let (domains, exception, selector) = if let Some((domains, selector)) = line.split_once("#@#") {
+        (domains, true, selector)
+    } else if let Some((domains, selector)) = line.split_once("##") {
+        (domains, false, selector)
+    } else {
+        return None;
+    };
+
+    let selector = selector.trim();
+    if selector.is_empty()
+        || selector.starts_with('^')
+        || selector.starts_with("+js(")
+        || UNSUPPORTED.iter().any(|syntax| selector.contains(syntax))
+    {
+        return Some(None);
+    }
+
+    let mut hiding = Hiding {
+        selector: selector.to_string(),
+        domains: Vec::new(),
+        excluded: Vec::new(),
+    };
+
+    for domain in domains.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+        // `example.*` stands for every top-level domain
+        if domain.contains('*') || !domain.is_ascii() {
+            return Some(None);
+        }
+        match domain.strip_prefix('~') {
+            Some(domain) => hiding.excluded.push(domain.to_string()),
+            None => hiding.domains.push(domain.to_string()),
+        }
+    }
+
+    Some(Some((exception, hiding)))
+}
+
+/// Applies `exceptions` to `hidings`, narrowing or dropping the rules they
+/// undo.
+pub fn apply_exceptions(mut hidings: Vec<Hiding>, exceptions: &[Hiding]) -> Vec<Hiding> {
+    for exception in exceptions {
+        hidings.retain_mut(|hiding| {
+            if hiding.selector != exception.selector {
+                return true;
+            }
+            if exception.domains.is_empty() {
+                return false;
+            }
+
+            if hiding.domains.is_empty() {
+                hiding.excluded.extend(exception.domains.iter().cloned());
+                true
+            } else {
+                hiding.domains.retain(|d| !exception.domains.contains(d));
+                !hiding.domains.is_empty()
+            }
+        });
+    }
+
+    hidings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hiding(line: &str) -> (bool, Hiding) {
+        parse(line).flatten().expect(line)
+    }
+
+    #[test]
+    fn generic_and_domain_rules() {
+        let (exception, generic) = hiding("##.ad-banner");
+        assert!(!exception);
+        assert_eq!(generic.selector, ".ad-banner");
+        assert!(generic.domains.is_empty());
+
+        let (_, specific) = hiding("example.org,~shop.example.org##div[id^=\"ad\"]");
+        assert_eq!(specific.selector, "div[id^=\"ad\"]");
+        assert_eq!(specific.domains, ["example.org"]);
+        assert_eq!(specific.excluded, ["shop.example.org"]);
+    }
+
+    #[test]
+    fn not_element_hiding() {
+        assert_eq!(parse("||ads.example.com^"), None);
+    }
+
+    #[test]
+    fn unsupported_syntax() {
+        for line in [
+            "example.org##div:has-text(Sponsored)",
+            "example.org#?#div:-abp-contains(ad)",
+            "example.org##+js(abort-on-property-read, ads)",
+            "example.org##^script:has-text(ads)",
+            "example.*##.ad",
+        ] {
+            assert_eq!(parse(line), Some(None), "{}", line);
+        }
+    }
+
+    #[test]
+    fn exceptions() {
+        let hidings = vec![
+            hiding("##.sponsored").1,
+            hiding("a.example,b.example##.promo").1,
+            hiding("a.example##.gone").1,
+            hiding("##.everywhere").1,
+        ];
+        let exceptions = [
+            hiding("c.example#@#.sponsored").1,
+            hiding("a.example#@#.promo").1,
+            hiding("a.example#@#.gone").1,
+            hiding("#@#.everywhere").1,
+        ];
+
+        let hidings = apply_exceptions(hidings, &exceptions);
+        assert_eq!(hidings.len(), 2);
+        assert_eq!(hidings[0].excluded, ["c.example"]);
+        assert_eq!(hidings[1].domains, ["b.example"]);
+    }
+}
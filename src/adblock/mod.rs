@@ -0,0 +1,224 @@
+//! Content blocking. Filter lists in Adblock Plus syntax (`*.txt` in the
+//! `adblock/` config directory) are converted into a single WebKit content
+//! filter, compiled once and shared by every profile's content manager.
+//! Network rules block requests; element hiding rules hide page elements.
+//! The filter also carries the `cookies` site settings, so it is compiled
+//! even without any lists.
+
+mod convert;
+mod cosmetic;
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+};
+
+use gtk4::gio;
+use webkit6::{UserContentFilter, UserContentFilterStore};
+
+use crate::{messages, paths, site_settings};
+
+// Compiled filters are stored under this prefix plus a hash of their rules
+const FILTER_ID_PREFIX: &str = "adblock-";
+
+const DISABLED_FILE: &str = "adblock-disabled";
+
+// Hiding rules added with `:hide-element`, in filter list syntax
+const CUSTOM_FILE: &str = "custom-filters.txt";
+
+thread_local! {
+    static FILTER: RefCell<Option<UserContentFilter>> = const { RefCell::new(None) };
+    static STARTED: Cell<bool> = const { Cell::new(false) };
+    // Bumped by each compile, so that only the latest one is installed
+    static GENERATION: Cell<u32> = const { Cell::new(0) };
+}
+
+/// The compiled filter, once it is ready.
+pub fn filter() -> Option<UserContentFilter> {
+    FILTER.with_borrow(|filter| filter.clone())
+}
+
+/// Compiles the filter lists the first time it is called.
+pub fn start(on_ready: impl FnOnce(UserContentFilter) + 'static) {
+    if !STARTED.replace(true) {
+        compile(on_ready);
+    }
+}
+
+/// Converts and compiles the filter lists, reusing an earlier compilation
+/// of exactly the same rules. Compiling a large list takes a few seconds, so
+/// the lists are read and converted on a worker thread and WebKit compiles
+/// them in the background; `on_ready` is called on the main thread. A
+/// compile started later supersedes this one.
+pub fn compile(on_ready: impl FnOnce(UserContentFilter) + 'static) {
+    let generation = GENERATION.get() + 1;
+    GENERATION.set(generation);
+    let disabled = disabled_sites();
+    let cookie_rules = site_settings::cookie_rules();
+
+    glib::spawn_future_local(async move {
+        let conversion = gio::spawn_blocking(move || {
+            let mut conversion = convert::convert(&read_lists(), &disabled);
+            append_rules(&mut conversion.json, &cookie_rules);
+            conversion
+        })
+        .await;
+        if let Ok(conversion) = conversion {
+            store_filter(conversion, generation, on_ready);
+        }
+    });
+}
+
+/// Adds `rules`, JSON objects, to the end of the rule list `json`.
+fn append_rules(json: &mut String, rules: &[String]) {
+    if rules.is_empty() {
+        return;
+    }
+    json.pop();
+    if json.len() > 1 {
+        json.push(',');
+    }
+    json.push_str(&rules.join(","));
+    json.push(']');
+}
+
+fn store_filter(
+    conversion: convert::Conversion,
+    generation: u32,
+    on_ready: impl FnOnce(UserContentFilter) + 'static,
+) {
+    if generation != GENERATION.get() {
+        return;
+    }
+    messages::debug(&format!(
+        "adblock: {} rules converted, {} skipped",
+        conversion.converted, conversion.skipped
+    ));
+
+    let mut hasher = DefaultHasher::new();
+    conversion.json.hash(&mut hasher);
+    let id = format!("{}{:016x}", FILTER_ID_PREFIX, hasher.finish());
+
+    let store =
+        UserContentFilterStore::new(&paths::cache_dir().join("content-filters").to_string_lossy());
+    let ready = move |filter: UserContentFilter| {
+        if generation != GENERATION.get() {
+            return;
+        }
+        FILTER.set(Some(filter.clone()));
+        on_ready(filter);
+    };
+
+    let (store_c, id_c) = (store.clone(), id.clone());
+    store.load(&id, None::<&gio::Cancellable>, move |result| {
+        if let Ok(filter) = result {
+            ready(filter);
+            return;
+        }
+
+        let json = glib::Bytes::from_owned(conversion.json.into_bytes());
+        let (store, id) = (store_c.clone(), id_c.clone());
+        store_c.save(
+            &id_c,
+            &json,
+            None::<&gio::Cancellable>,
+            move |result| match result {
+                Ok(filter) => {
+                    if generation == GENERATION.get() {
+                        remove_stale(&store, &id);
+                    }
+                    ready(filter);
+                }
+                Err(err) => messages::error(&format!("Failed to compile content filter: {}", err)),
+            },
+        );
+    });
+}
+
+/// Drops compilations of rules that have since changed.
+fn remove_stale(store: &UserContentFilterStore, current: &str) {
+    let current = current.to_string();
+    let store_c = store.clone();
+
+    store.fetch_identifiers(None::<&gio::Cancellable>, move |ids| {
+        for id in ids {
+            if id.starts_with(FILTER_ID_PREFIX) && id != current {
+                store_c.remove(&id, None::<&gio::Cancellable>, |result| {
+                    if let Err(err) = result {
+                        messages::debug(&format!(
+                            "Failed to remove a stale content filter: {}",
+                            err
+                        ));
+                    }
+                });
+            }
+        }
+    });
+}
+
+/// The filter lists from the config directory, then the custom rules.
+fn read_lists() -> String {
+    let mut files: Vec<_> = fs::read_dir(paths::config_dir().join("adblock"))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files.push(paths::data_file(CUSTOM_FILE));
+
+    files
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Saves `host##selector` so the element stays hidden on later visits.
+/// The filter has to be recompiled.
+pub fn add_hiding_rule(host: &str, selector: &str) {
+    let mut rules = paths::read_list(CUSTOM_FILE);
+    rules.push(format!("{}##{}", site(host), selector));
+
+    if let Err(err) = paths::write_list(CUSTOM_FILE, &rules) {
+        messages::error(&format!("Failed to save hiding rule: {}", err));
+    }
+}
+
+fn disabled_sites() -> Vec<String> {
+    paths::read_list(DISABLED_FILE)
+}
+
+pub fn is_disabled_for(host: &str) -> bool {
+    let host = site(host);
+    disabled_sites()
+        .iter()
+        .any(|site| host == *site || host.ends_with(&format!(".{}", site)))
+}
+
+/// Turns blocking off for `host` (and its subdomains), or back on.
+/// Returns whether blocking is now on. The filter has to be recompiled.
+pub fn toggle_site(host: &str) -> bool {
+    let host = site(host);
+    let mut sites = disabled_sites();
+
+    let was_disabled = sites.iter().any(|site| *site == host);
+    sites.retain(|site| *site != host);
+    if !was_disabled {
+        sites.push(host.to_string());
+    }
+
+    if let Err(err) = paths::write_list(DISABLED_FILE, &sites) {
+        messages::error(&format!("Failed to save adblock state: {}", err));
+    }
+    was_disabled
+}
+
+fn site(host: &str) -> &str {
+    host.strip_prefix("www.").unwrap_or(host)
+}
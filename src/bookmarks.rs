@@ -0,0 +1,44 @@
+//! Bookmarked pages, shown on the start page.
+
+use crate::{history::Page, messages, paths};
+
+const BOOKMARKS_FILE: &str = "bookmarks";
+
+/// Bookmarks in the order they were added. One per line: <uri>\t<title>
+pub fn all() -> Vec<Page> {
+    paths::read_list(BOOKMARKS_FILE)
+        .iter()
+        .filter_map(|line| {
+            let (uri, title) = line.split_once('\t').unwrap_or((line, ""));
+            (!uri.is_empty()).then(|| Page {
+                uri: uri.to_string(),
+                title: title.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Bookmarks `uri`, or removes the bookmark it already has. Returns
+/// whether it is bookmarked now.
+pub fn toggle(uri: &str, title: &str) -> bool {
+    let mut bookmarks = all();
+    let was_bookmarked = bookmarks.iter().any(|page| page.uri == uri);
+
+    if was_bookmarked {
+        bookmarks.retain(|page| page.uri != uri);
+    } else {
+        bookmarks.push(Page {
+            uri: uri.to_string(),
+            title: title.replace(['\t', '\n'], " "),
+        });
+    }
+
+    let lines: Vec<String> = bookmarks
+        .iter()
+        .map(|page| format!("{}\t{}", page.uri, page.title))
+        .collect();
+    if let Err(err) = paths::write_list(BOOKMARKS_FILE, &lines) {
+        messages::error(&format!("Failed to save bookmarks: {}", err));
+    }
+    !was_bookmarked
+}
@@ -0,0 +1,157 @@
+//! What tilde remembers about browsing for the start page: how often each
+//! page was visited, and the tabs closed in this run.
+
+use std::cell::{Cell, RefCell};
+
+use crate::{messages, paths};
+
+const VISITS_FILE: &str = "visits";
+// Least visited pages are dropped beyond this
+const VISITS_LIMIT: usize = 1000;
+// Visits are written out at most this often
+const SAVE_DELAY_SECONDS: u32 = 30;
+const CLOSED_LIMIT: usize = 20;
+
+#[derive(Clone, Debug)]
+pub struct Page {
+    pub uri: String,
+    pub title: String,
+}
+
+#[derive(Clone, Debug)]
+struct Visits {
+    page: Page,
+    count: u32,
+}
+
+thread_local! {
+    // Most visited first
+    static VISITS: RefCell<Option<Vec<Visits>>> = const { RefCell::new(None) };
+    // Most recently closed first
+    static CLOSED: RefCell<Vec<Page>> = const { RefCell::new(Vec::new()) };
+    static SAVE_PENDING: Cell<bool> = const { Cell::new(false) };
+}
+
+fn with_visits<T>(f: impl FnOnce(&mut Vec<Visits>) -> T) -> T {
+    VISITS.with_borrow_mut(|visits| f(visits.get_or_insert_with(load_visits)))
+}
+
+/// One page per line: <count>\t<uri>\t<title>
+fn load_visits() -> Vec<Visits> {
+    paths::read_list(VISITS_FILE)
+        .iter()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let count = fields.next()?.parse().ok()?;
+            let uri = fields.next()?.to_string();
+            let title = fields.next().unwrap_or_default().to_string();
+            Some(Visits {
+                page: Page { uri, title },
+                count,
+            })
+        })
+        .collect()
+}
+
+fn save_visits(visits: &[Visits]) {
+    let lines: Vec<String> = visits
+        .iter()
+        .map(|v| format!("{}\t{}\t{}", v.count, v.page.uri, v.page.title))
+        .collect();
+    if let Err(err) = paths::write_list(VISITS_FILE, &lines) {
+        messages::error(&format!("Failed to save visits: {}", err));
+    }
+}
+
+fn save_later() {
+    if SAVE_PENDING.replace(true) {
+        return;
+    }
+    glib::timeout_add_seconds_local_once(SAVE_DELAY_SECONDS, save_pending);
+}
+
+/// Writes out visits that haven't been saved yet, as when tilde quits.
+pub fn save_pending() {
+    if SAVE_PENDING.replace(false) {
+        VISITS.with_borrow(|visits| {
+            if let Some(visits) = visits {
+                save_visits(visits);
+            }
+        });
+    }
+}
+
+/// Only web pages count, not tilde's own or blank ones.
+fn is_web_page(uri: &str) -> bool {
+    uri.starts_with("https://") || uri.starts_with("http://")
+}
+
+/// Counts a visit to `uri`, keeping the latest title. A new page takes the
+/// place of the least visited one once there are too many; of those, the
+/// one that has been least visited the longest goes.
+pub fn record_visit(uri: &str, title: &str) {
+    if !is_web_page(uri) {
+        return;
+    }
+    // Tabs and newlines would break the file format
+    let title = title.replace(['\t', '\n'], " ");
+
+    with_visits(|visits| {
+        match visits.iter_mut().find(|v| v.page.uri == uri) {
+            Some(v) => {
+                v.count += 1;
+                if !title.is_empty() {
+                    v.page.title = title;
+                }
+            }
+            None => {
+                while visits.len() >= VISITS_LIMIT {
+                    // Ties are in the order they reached their count, so the
+                    // first with the lowest count has had it the longest
+                    let lowest = visits.last().map_or(0, |v| v.count);
+                    let pos = visits.iter().position(|v| v.count == lowest);
+                    visits.remove(pos.unwrap_or(0));
+                }
+                visits.push(Visits {
+                    page: Page {
+                        uri: uri.to_string(),
+                        title,
+                    },
+                    count: 1,
+                })
+            }
+        }
+
+        // Stable, so ties keep the earlier visited page first
+        visits.sort_by(|a, b| b.count.cmp(&a.count));
+    });
+    save_later();
+}
+
+/// The `n` most visited pages.
+pub fn most_visited(n: usize) -> Vec<Page> {
+    with_visits(|visits| visits.iter().take(n).map(|v| v.page.clone()).collect())
+}
+
+pub fn record_closed(uri: &str, title: &str) {
+    if !is_web_page(uri) {
+        return;
+    }
+
+    CLOSED.with_borrow_mut(|closed| {
+        closed.retain(|page| page.uri != uri);
+        closed.insert(
+            0,
+            Page {
+                uri: uri.to_string(),
+                title: title.to_string(),
+            },
+        );
+        closed.truncate(CLOSED_LIMIT);
+    });
+}
+
+/// Tabs closed since tilde started, most recent first.
+pub fn recently_closed() -> Vec<Page> {
+    CLOSED.with_borrow(|closed| closed.clone())
+}
@@ -1,3 +1,16 @@
+mod adblock;
+mod bookmarks;
+mod history;
+mod messages;
+mod pages;
+mod paths;
+mod profile;
+mod scripts;
+mod session;
+mod settings;
+mod site_settings;
+mod stylesheets;
+mod userscripts;
 mod window;
 
 use gtk4::Application;
@@ -12,11 +25,27 @@ fn main() -> glib::ExitCode {
 
     let app = Application::builder().application_id(APP_ID).build();
     app.connect_activate(build_ui);
+    app.connect_shutdown(|_| history::save_pending());
 
     app.run()
 }
 
 fn build_ui(app: &Application) {
-    let window = Window::new(app);
-    window.present();
+    // Launching again while running just brings the browser forward
+    if let Some(window) = app.active_window() {
+        window.present();
+        return;
+    }
+
+    if Window::run_benchmark_from_env(app) {
+        return;
+    }
+
+    let windows = Window::restore_session(app);
+    if windows.is_empty() {
+        Window::new(app).present();
+    }
+    for window in windows {
+        window.present();
+    }
 }
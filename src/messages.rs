@@ -0,0 +1,188 @@
+//! Messages for the user: feedback from commands, warnings and errors.
+//! Windows show them in the dock, `:messages` lists the ones from this run,
+//! and those at or above `log.level` are appended to the log file.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    fmt,
+    fs::{self, OpenOptions},
+    io::Write as _,
+    rc::Rc,
+};
+
+use crate::{paths, settings};
+
+const LOG_FILE: &str = "log";
+// The log file is started afresh beyond this many bytes
+const LOG_FILE_LIMIT: u64 = 1024 * 1024;
+// Messages kept for `:messages`
+const LOG_LIMIT: usize = 200;
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Level {
+    /// Only written to the log file
+    Debug,
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub level: Level,
+    pub text: String,
+    pub time: glib::DateTime,
+}
+
+impl Message {
+    /// Hours, minutes and seconds, for showing next to the text.
+    pub fn time_of_day(&self) -> String {
+        self.time
+            .format("%H:%M:%S")
+            .map(|time| time.to_string())
+            .unwrap_or_default()
+    }
+}
+
+type Listener = Rc<dyn Fn(&Message)>;
+
+/// Identifies a listener added with `connect`, to remove it again.
+pub struct ListenerId(u64);
+
+thread_local! {
+    // Oldest first
+    static LOG: RefCell<VecDeque<Message>> = const { RefCell::new(VecDeque::new()) };
+    static LISTENERS: RefCell<Vec<(u64, Listener)>> = const { RefCell::new(Vec::new()) };
+    static NEXT_LISTENER_ID: Cell<u64> = const { Cell::new(0) };
+    static LOG_FILE_CHECKED: Cell<bool> = const { Cell::new(false) };
+}
+
+pub fn debug(text: &str) {
+    log(Level::Debug, text);
+}
+
+pub fn info(text: &str) {
+    log(Level::Info, text);
+}
+
+pub fn warning(text: &str) {
+    log(Level::Warning, text);
+}
+
+pub fn error(text: &str) {
+    log(Level::Error, text);
+}
+
+/// Reports `text` at `level`.
+pub fn log(level: Level, text: &str) {
+    let message = Message {
+        level,
+        text: text.to_string(),
+        time: glib::DateTime::now_local().expect("local time is known"),
+    };
+
+    write_to_file(&message);
+    if level >= Level::Warning {
+        eprintln!("{}: {}", level, text);
+    }
+    if level == Level::Debug {
+        return;
+    }
+
+    LOG.with_borrow_mut(|log| {
+        log.push_back(message.clone());
+        if log.len() > LOG_LIMIT {
+            log.pop_front();
+        }
+    });
+
+    // Later, so that whatever reported the message has finished with any
+    // state the listeners might look at
+    glib::idle_add_local_once(move || {
+        let listeners = LISTENERS.with_borrow(|listeners| listeners.clone());
+        for (_, listener) in listeners {
+            listener(&message);
+        }
+    });
+}
+
+/// The messages shown so far, oldest first. Debug messages are only in the
+/// log file.
+pub fn all() -> Vec<Message> {
+    LOG.with_borrow(|log| log.iter().cloned().collect())
+}
+
+/// Calls `f` with every message from now on, except debug ones, until
+/// `disconnect` is called with the returned id.
+pub fn connect(f: impl Fn(&Message) + 'static) -> ListenerId {
+    let id = NEXT_LISTENER_ID.get();
+    NEXT_LISTENER_ID.set(id + 1);
+    LISTENERS.with_borrow_mut(|listeners| listeners.push((id, Rc::new(f))));
+    ListenerId(id)
+}
+
+pub fn disconnect(id: ListenerId) {
+    LISTENERS.with_borrow_mut(|listeners| listeners.retain(|(other, _)| *other != id.0));
+}
+
+/// The lowest level written to the log file, or `None` for no logging.
+fn file_level() -> Option<Level> {
+    match settings::text("log.level").as_str() {
+        "debug" => Some(Level::Debug),
+        "info" => Some(Level::Info),
+        "warning" => Some(Level::Warning),
+        "error" => Some(Level::Error),
+        _ => None,
+    }
+}
+
+/// One line per message: <time>\t<level>\t<text>, with newlines in the
+/// text escaped.
+fn write_to_file(message: &Message) {
+    if file_level().is_none_or(|level| message.level < level) {
+        return;
+    }
+
+    let path = paths::data_file(LOG_FILE);
+    if !LOG_FILE_CHECKED.replace(true)
+        && fs::metadata(&path).is_ok_and(|metadata| metadata.len() > LOG_FILE_LIMIT)
+    {
+        let _ = fs::rename(&path, path.with_extension("old"));
+    }
+
+    let line = format!(
+        "{}\t{}\t{}\n",
+        message.time.format_iso8601().unwrap_or_default(),
+        message.level,
+        message.text.replace('\\', "\\\\").replace('\n', "\\n")
+    );
+    let written = fs::create_dir_all(paths::data_dir()).and_then(|_| {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?
+            .write_all(line.as_bytes())
+    });
+    if let Err(err) = written {
+        // Nowhere else to report it
+        eprintln!("Failed to write {}: {}", path.display(), err);
+    }
+}
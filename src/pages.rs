@@ -0,0 +1,241 @@
+//! Built-in `tilde://` pages. They are rendered locally for each request,
+//! and the scheme is registered as local so web pages can't load them.
+
+use gtk4::gio;
+use webkit6::{URISchemeRequest, WebContext};
+
+use crate::{
+    bookmarks,
+    history::{self, Page},
+    settings::{self, Kind, Value},
+};
+
+pub const SCHEME: &str = "tilde";
+
+pub const SETTINGS_URI: &str = "tilde://settings";
+
+// Entries per list on the start page
+const START_PAGE_ITEMS: usize = 12;
+
+pub fn register(web_context: &WebContext) {
+    web_context.register_uri_scheme(SCHEME, handle);
+    if let Some(security_manager) = web_context.security_manager() {
+        security_manager.register_uri_scheme_as_local(SCHEME);
+    }
+}
+
+fn handle(request: &URISchemeRequest) {
+    let page = request
+        .uri()
+        .and_then(|uri| glib::Uri::parse(&uri, glib::UriFlags::NONE).ok())
+        .and_then(|uri| uri.host())
+        .map(|host| host.to_string())
+        .unwrap_or_default();
+
+    let html = match page.as_str() {
+        "settings" => settings_page(),
+        "start" => start_page(),
+        _ => render(
+            "Not found",
+            &format!(
+                "<h1>Not found</h1><p>There is no <code>{}://{}</code> page.</p>",
+                SCHEME,
+                escape(&page)
+            ),
+        ),
+    };
+
+    let bytes = glib::Bytes::from_owned(html.into_bytes());
+    let length = bytes.len() as i64;
+    let stream = gio::MemoryInputStream::from_bytes(&bytes);
+    request.finish(&stream, length, Some("text/html"));
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// A file of the resource bundle as text, or nothing if it is missing.
+fn resource(path: &str) -> String {
+    gio::resources_lookup_data(path, gio::ResourceLookupFlags::NONE)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes.to_vec()).ok())
+        .unwrap_or_default()
+}
+
+/// A complete page with the shared style.
+fn render(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{}</title>
+<style>{}</style>
+</head>
+<body>
+{}
+</body>
+</html>",
+        escape(title),
+        resource("/templates/pages/page.css"),
+        body
+    )
+}
+
+/// Every setting with its value, editable in place. Changes are sent to
+/// tilde by `settings_script`.
+fn settings_page() -> String {
+    let mut body = String::from("<h1>Settings</h1>");
+    body.push_str(&format!(
+        "<p class=\"muted\">Changes apply straight away and are saved to <code>{}</code>.</p><table>",
+        escape(&settings::path().display().to_string())
+    ));
+
+    for info in settings::SETTINGS {
+        let name = escape(info.name);
+        let value = settings::get(info.name);
+        let is_default = settings::is_default(info.name);
+
+        let input = match (info.kind, &value) {
+            (Kind::Bool, Value::Bool(checked)) => format!(
+                "<input type=\"checkbox\" data-setting=\"{}\"{}>",
+                name,
+                if *checked { " checked" } else { "" }
+            ),
+            (Kind::Choice(choices), _) => {
+                let current = value.to_string();
+                let options: String = choices
+                    .iter()
+                    .map(|choice| {
+                        format!(
+                            "<option{}>{}</option>",
+                            if *choice == current { " selected" } else { "" },
+                            escape(choice)
+                        )
+                    })
+                    .collect();
+                format!("<select data-setting=\"{}\">{}</select>", name, options)
+            }
+            _ => format!(
+                "<input type=\"text\" data-setting=\"{}\" value=\"{}\">",
+                name,
+                escape(&value.to_string())
+            ),
+        };
+        let reset = if is_default {
+            String::new()
+        } else {
+            format!(
+                "<button data-reset=\"{}\" title=\"Default: {}\">Reset</button>",
+                name,
+                escape(info.default)
+            )
+        };
+
+        body.push_str(&format!(
+            "<tr id=\"{name}\"{}><td><code>{name}</code><div class=\"muted\">{}</div></td><td>{}</td><td>{}</td></tr>",
+            if is_default { "" } else { " class=\"changed\"" },
+            escape(info.description),
+            input,
+            reset,
+        ));
+    }
+
+    body.push_str("</table>");
+    render("Settings", &body)
+}
+
+/// The script that wires up the inputs of `tilde://settings`. It is added
+/// to tilde's own script world rather than the page.
+pub fn settings_script() -> String {
+    resource("/templates/pages/settings.js")
+}
+
+/// Shown in place of `uri` after its web process went away, e.g. "crashed".
+pub fn crash_page(uri: &str, what_happened: &str) -> String {
+    render(
+        "Page crashed",
+        &format!(
+            "<h1>This page {}</h1>
+<p class=\"error\"><code>{}</code></p>
+<p><a href=\"{}\">Reload the page</a> or press <code>r</code>.</p>",
+            escape(what_happened),
+            escape(uri),
+            escape(uri)
+        ),
+    )
+}
+
+/// Shown in place of `uri` when it failed to load. `details` are lines of
+/// plain text; `keys` pairs a key with what it does on this page.
+pub fn error_page(uri: &str, heading: &str, details: &[String], keys: &[(&str, &str)]) -> String {
+    let mut body = format!(
+        "<h1>{}</h1><p class=\"error\"><code>{}</code></p>",
+        escape(heading),
+        escape(uri)
+    );
+    for line in details {
+        body.push_str(&format!("<p>{}</p>", escape(line)));
+    }
+
+    body.push_str("<ul class=\"links\">");
+    for (key, action) in keys {
+        body.push_str(&format!(
+            "<li><code>{}</code> {}</li>",
+            escape(key),
+            escape(action)
+        ));
+    }
+    body.push_str("</ul>");
+    render(heading, &body)
+}
+
+/// Frequently visited pages, bookmarks and recently closed tabs.
+fn start_page() -> String {
+    let sections = [
+        ("Most visited", history::most_visited(START_PAGE_ITEMS)),
+        ("Bookmarks", bookmarks::all()),
+        ("Recently closed", history::recently_closed()),
+    ];
+
+    let mut body = String::new();
+    for (heading, pages) in sections {
+        body.push_str(&format!("<h2>{}</h2>", heading));
+        if pages.is_empty() {
+            body.push_str("<p class=\"muted\">Nothing yet</p>");
+            continue;
+        }
+
+        body.push_str("<ul class=\"links\">");
+        for page in pages.iter().take(START_PAGE_ITEMS) {
+            body.push_str(&page_link(page));
+        }
+        body.push_str("</ul>");
+    }
+    render("New tab", &body)
+}
+
+fn page_link(page: &Page) -> String {
+    let host = glib::Uri::parse(&page.uri, glib::UriFlags::NONE)
+        .ok()
+        .and_then(|uri| uri.host())
+        .map(|host| host.to_string())
+        .unwrap_or_default();
+    let title = if page.title.is_empty() {
+        &page.uri
+    } else {
+        &page.title
+    };
+
+    format!(
+        "<li><a href=\"{}\">{}</a> <span class=\"muted\">{}</span></li>",
+        escape(&page.uri),
+        escape(title),
+        escape(&host)
+    )
+}
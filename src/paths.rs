@@ -0,0 +1,45 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const APP_DIR: &str = "tilde";
+
+/// Directory for state tilde writes itself (marks, sessions, ...).
+pub fn data_dir() -> PathBuf {
+    glib::user_data_dir().join(APP_DIR)
+}
+
+/// Directory for files the user writes to customise tilde.
+pub fn config_dir() -> PathBuf {
+    glib::user_config_dir().join(APP_DIR)
+}
+
+/// Directory for caches that can be thrown away at any time.
+pub fn cache_dir() -> PathBuf {
+    glib::user_cache_dir().join(APP_DIR)
+}
+
+pub fn data_file(name: &str) -> PathBuf {
+    data_dir().join(name)
+}
+
+/// Lines of the data file `name`, or nothing if it does not exist.
+pub fn read_list(name: &str) -> Vec<String> {
+    fs::read_to_string(data_file(name))
+        .map(|contents| contents.lines().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+pub fn write_list(name: &str, lines: &[String]) -> io::Result<()> {
+    let contents: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+    write_file(&data_file(name), &contents)
+}
+
+/// Writes `contents` to `path`, creating parent directories as needed.
+pub fn write_file(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)
+}
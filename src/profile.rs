@@ -0,0 +1,414 @@
+//! Browser profiles. All tabs of a profile share one web context, network
+//! session and user content manager, so page scripts are compiled and
+//! message handlers registered once per profile instead of once per tab.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    path::PathBuf,
+    rc::{Rc, Weak},
+};
+
+use glib::{KeyFile, KeyFileFlags, SignalHandlerId, WeakRef, object::ObjectExt as _};
+use gtk4::{
+    gio::{self, prelude::TlsCertificateExt as _},
+    prelude::WidgetExt as _,
+};
+use webkit6::{
+    CookieAcceptPolicy, LoadEvent, NetworkSession, UserContentFilter, UserContentInjectedFrames,
+    UserContentManager, UserScript, UserScriptInjectionTime, WebContext, WebView, javascriptcore,
+    prelude::WebViewExt as _,
+};
+
+use crate::{
+    adblock,
+    messages::{self, Level},
+    pages, paths,
+    scripts::{self, PAGE_SCRIPT_WORLD},
+    stylesheets, userscripts, window,
+};
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+// Certificates accepted despite errors, one group per host
+const CERTIFICATES_FILE: &str = "certificates";
+
+thread_local! {
+    static PROFILES: RefCell<HashMap<String, Rc<Profile>>> = RefCell::default();
+    static STYLE_RELOAD_PENDING: Cell<bool> = const { Cell::new(false) };
+    // Worlds of the installed userscripts, which are the same in every
+    // profile
+    static USERSCRIPT_WORLDS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+pub struct Profile {
+    pub name: String,
+    pub web_context: WebContext,
+    pub network_session: NetworkSession,
+    pub content_manager: UserContentManager,
+    // Tab id -> view, for routing script messages
+    views: RefCell<HashMap<u64, WeakRef<WebView>>>,
+    // Userscript id -> its message handlers' connections
+    userscript_handlers: RefCell<Vec<(String, Vec<SignalHandlerId>)>>,
+}
+
+impl Profile {
+    /// The profile called `name`, set up on first use.
+    pub fn get(name: &str) -> Rc<Profile> {
+        PROFILES.with_borrow_mut(|profiles| {
+            profiles
+                .entry(name.to_string())
+                .or_insert_with(|| Profile::new(name))
+                .clone()
+        })
+    }
+
+    fn new(name: &str) -> Rc<Profile> {
+        let data_dir = paths::data_dir().join("profiles").join(name);
+        let cache_dir = paths::cache_dir().join("profiles").join(name);
+        let network_session = NetworkSession::new(data_dir.to_str(), cache_dir.to_str());
+
+        let web_context = WebContext::new();
+        pages::register(&web_context);
+
+        let profile = Rc::new_cyclic(|profile: &Weak<Profile>| {
+            let content_manager = UserContentManager::new();
+
+            content_manager.register_script_message_handler("editState", Some(PAGE_SCRIPT_WORLD));
+            let weak = profile.clone();
+            content_manager.connect_script_message_received(Some("editState"), move |_, msg| {
+                let Some(profile) = weak.upgrade() else {
+                    return;
+                };
+                let editable = msg
+                    .object_get_property("editable")
+                    .is_some_and(|editable| editable.to_boolean());
+                let from_frame = msg
+                    .object_get_property("frame")
+                    .is_some_and(|frame| frame.to_boolean());
+
+                let webview = if from_frame {
+                    profile.focused_view()
+                } else {
+                    profile.view_for(message_tab(msg))
+                };
+                if let Some(webview) = webview {
+                    window::show_edit_state(&webview, editable);
+                }
+            });
+
+            content_manager.register_script_message_handler("adblock", Some(PAGE_SCRIPT_WORLD));
+            let weak = profile.clone();
+            content_manager.connect_script_message_received(Some("adblock"), move |_, msg| {
+                let Some(profile) = weak.upgrade() else {
+                    return;
+                };
+                let blocked = msg
+                    .object_get_property("blocked")
+                    .filter(|blocked| blocked.is_number())
+                    .map_or(0, |blocked| blocked.to_double() as u32);
+
+                if let Some(webview) = profile.view_for(message_tab(msg)) {
+                    window::show_blocked_count(&webview, blocked);
+                }
+            });
+
+            content_manager.register_script_message_handler("scroll", Some(PAGE_SCRIPT_WORLD));
+            let weak = profile.clone();
+            content_manager.connect_script_message_received(Some("scroll"), move |_, msg| {
+                let Some(profile) = weak.upgrade() else {
+                    return;
+                };
+                let percent = msg
+                    .object_get_property("percent")
+                    .filter(|percent| percent.is_number())
+                    .map(|percent| percent.to_double())
+                    .filter(|percent| *percent >= 0.0)
+                    .map(|percent| percent.min(100.0) as u32);
+
+                if let Some(webview) = profile.view_for(message_tab(msg)) {
+                    window::show_scroll_percent(&webview, percent);
+                }
+            });
+
+            content_manager.register_script_message_handler("settings", Some(PAGE_SCRIPT_WORLD));
+            let weak = profile.clone();
+            content_manager.connect_script_message_received(Some("settings"), move |_, msg| {
+                let Some(profile) = weak.upgrade() else {
+                    return;
+                };
+                if let Some(webview) = profile.view_for(message_tab(msg)) {
+                    window::change_setting(&webview, msg);
+                }
+            });
+
+            Profile {
+                name: name.to_string(),
+                web_context,
+                network_session,
+                content_manager,
+                views: RefCell::default(),
+                userscript_handlers: RefCell::default(),
+            }
+        });
+
+        profile.install_scripts();
+        profile.install_style_sheets();
+        profile.load_certificate_exceptions();
+        stylesheets::watch(reload_style_sheets_later);
+
+        match adblock::filter() {
+            Some(filter) => profile.use_filter(&filter),
+            None => adblock::start(|filter| install_filter(&filter)),
+        }
+        profile
+    }
+
+    /// Filters content with `filter` instead of any earlier one. The filter
+    /// blocks cookies per site, so from then on the session itself accepts
+    /// them all; until then it keeps WebKit's no-third-party default.
+    fn use_filter(&self, filter: &UserContentFilter) {
+        self.content_manager.remove_all_filters();
+        self.content_manager.add_filter(filter);
+        if let Some(cookie_manager) = self.network_session.cookie_manager() {
+            cookie_manager.set_accept_policy(CookieAcceptPolicy::Always);
+        }
+    }
+
+    fn certificates_path(&self) -> PathBuf {
+        paths::data_dir()
+            .join("profiles")
+            .join(&self.name)
+            .join(CERTIFICATES_FILE)
+    }
+
+    fn certificate_exceptions(&self) -> KeyFile {
+        let file = KeyFile::new();
+        let _ = file.load_from_file(self.certificates_path(), KeyFileFlags::NONE);
+        file
+    }
+
+    fn load_certificate_exceptions(&self) {
+        let file = self.certificate_exceptions();
+
+        for host in file.groups().iter() {
+            let certificate = file
+                .string(host, "pem")
+                .ok()
+                .and_then(|pem| gio::TlsCertificate::from_pem(&pem).ok());
+            match certificate {
+                Some(certificate) => self
+                    .network_session
+                    .allow_tls_certificate_for_host(&certificate, host),
+                None => messages::warning(&format!(
+                    "{} [{}]: not a certificate",
+                    CERTIFICATES_FILE, host
+                )),
+            }
+        }
+    }
+
+    /// Trusts `certificate` for `host` in this profile, now and in later
+    /// runs, whatever is wrong with it.
+    pub fn allow_certificate(&self, host: &str, certificate: &gio::TlsCertificate) {
+        self.network_session
+            .allow_tls_certificate_for_host(certificate, host);
+
+        let Some(pem) = certificate.certificate_pem() else {
+            return;
+        };
+        let file = self.certificate_exceptions();
+        file.set_string(host, "pem", &pem);
+        if let Err(err) = paths::write_file(&self.certificates_path(), &file.to_data()) {
+            messages::error(&format!("Failed to save certificate exception: {}", err));
+        }
+    }
+
+    /// (Re)adds every enabled user stylesheet. Unlike scripts, these apply
+    /// to open pages straight away.
+    fn install_style_sheets(&self) {
+        let content_manager = &self.content_manager;
+        content_manager.remove_all_style_sheets();
+
+        for sheet in stylesheets::load_all().iter().filter(|s| s.enabled) {
+            content_manager.add_style_sheet(&sheet.user_style_sheet());
+        }
+    }
+
+    /// (Re)adds the page scripts and every enabled userscript. Only pages
+    /// loaded afterwards see the change.
+    fn install_scripts(self: &Rc<Self>) {
+        let content_manager = &self.content_manager;
+        content_manager.remove_all_scripts();
+        self.unregister_userscript_handlers();
+
+        for source in scripts::compose() {
+            content_manager.add_script(&UserScript::for_world(
+                &source,
+                UserContentInjectedFrames::AllFrames,
+                UserScriptInjectionTime::Start,
+                PAGE_SCRIPT_WORLD,
+                &[],
+                &[],
+            ));
+        }
+
+        content_manager.add_script(&UserScript::for_world(
+            &pages::settings_script(),
+            UserContentInjectedFrames::TopFrame,
+            UserScriptInjectionTime::End,
+            PAGE_SCRIPT_WORLD,
+            &[],
+            &[],
+        ));
+
+        let mut worlds = Vec::new();
+        for userscript in userscripts::load_all().iter().filter(|s| s.enabled) {
+            if let Some(script) = userscript.user_script() {
+                content_manager.add_script(&script);
+                self.register_userscript_handlers(&userscript.id);
+                worlds.push(userscripts::world(&userscript.id));
+            }
+        }
+        USERSCRIPT_WORLDS.set(worlds);
+    }
+
+    /// Registers the message handlers of userscript `id` in its world. The
+    /// script is known by which handler a message arrives on, never by what
+    /// the message says.
+    fn register_userscript_handlers(self: &Rc<Self>, id: &str) {
+        let content_manager = &self.content_manager;
+        let world = userscripts::world(id);
+        let gm = userscripts::handler_name(userscripts::GM_HANDLER, id);
+        let values = userscripts::handler_name(userscripts::VALUES_HANDLER, id);
+
+        content_manager.register_script_message_handler(&gm, Some(&world));
+        let weak = Rc::downgrade(self);
+        let script = id.to_string();
+        let on_gm = content_manager.connect_script_message_received(Some(&gm), move |_, msg| {
+            let Some(profile) = weak.upgrade() else {
+                return;
+            };
+            window::handle_gm_message(profile.view_for(message_tab(msg)), &script, msg);
+        });
+
+        // Userscripts ask for their stored values as they start, so they
+        // see values written since the scripts were added
+        content_manager.register_script_message_handler_with_reply(&values, &world);
+        let script = id.to_string();
+        let on_values = content_manager.connect_script_message_with_reply_received(
+            Some(&values),
+            move |_, msg, reply| {
+                window::reply_gm_values(&script, msg, reply);
+                true
+            },
+        );
+
+        self.userscript_handlers
+            .borrow_mut()
+            .push((id.to_string(), vec![on_gm, on_values]));
+    }
+
+    fn unregister_userscript_handlers(&self) {
+        let content_manager = &self.content_manager;
+
+        for (id, connections) in self.userscript_handlers.take() {
+            for connection in connections {
+                content_manager.disconnect(connection);
+            }
+            let world = userscripts::world(&id);
+            for handler in [userscripts::GM_HANDLER, userscripts::VALUES_HANDLER] {
+                content_manager.unregister_script_message_handler(
+                    &userscripts::handler_name(handler, &id),
+                    Some(&world),
+                );
+            }
+        }
+    }
+
+    /// Registers `webview` as tab `id` so its pages' messages reach it. The
+    /// id is handed to the top frame of each page once it is committed, in
+    /// the script worlds only, where the page can't change it.
+    pub fn add_view(&self, id: u64, webview: &WebView) {
+        let mut views = self.views.borrow_mut();
+        views.retain(|_, view| view.upgrade().is_some());
+        views.insert(id, webview.downgrade());
+
+        webview.connect_load_changed(move |webview, event| {
+            if event == LoadEvent::Committed {
+                let mut worlds = vec![PAGE_SCRIPT_WORLD.to_string()];
+                USERSCRIPT_WORLDS.with_borrow(|userscripts| worlds.extend_from_slice(userscripts));
+
+                for world in &worlds {
+                    webview.evaluate_javascript(
+                        &format!("window.__tildeTab = {};", id),
+                        Some(world),
+                        None,
+                        None::<&gio::Cancellable>,
+                        scripts::report_failure(Level::Debug, "Telling the page its tab id"),
+                    );
+                }
+            }
+        });
+    }
+
+    /// The view a message came from. Messages sent before the page knew its
+    /// tab id, or by subframes, can't be placed and are dropped.
+    fn view_for(&self, tab: Option<u64>) -> Option<WebView> {
+        self.views.borrow().get(&tab?)?.upgrade()
+    }
+
+    /// The view with keyboard focus. Subframes don't know their tab, but
+    /// only the focused view's frames can take focus from the user, so
+    /// that is where their edit state belongs.
+    fn focused_view(&self) -> Option<WebView> {
+        self.views
+            .borrow()
+            .values()
+            .filter_map(|view| view.upgrade())
+            .find(|view| view.has_focus())
+    }
+}
+
+/// Reinstalls the scripts of every profile, e.g. after a userscript was
+/// toggled.
+pub fn reload_scripts() {
+    PROFILES.with_borrow(|profiles| {
+        for profile in profiles.values() {
+            profile.install_scripts();
+        }
+    });
+}
+
+/// Replaces the content filter of every profile with `filter`.
+pub fn install_filter(filter: &UserContentFilter) {
+    PROFILES.with_borrow(|profiles| {
+        for profile in profiles.values() {
+            profile.use_filter(filter);
+        }
+    });
+}
+
+pub fn reload_style_sheets() {
+    PROFILES.with_borrow(|profiles| {
+        for profile in profiles.values() {
+            profile.install_style_sheets();
+        }
+    });
+}
+
+fn reload_style_sheets_later() {
+    if STYLE_RELOAD_PENDING.replace(true) {
+        return;
+    }
+    glib::idle_add_local_once(|| {
+        STYLE_RELOAD_PENDING.set(false);
+        reload_style_sheets();
+    });
+}
+
+fn message_tab(msg: &javascriptcore::Value) -> Option<u64> {
+    msg.object_get_property("tab")
+        .filter(|tab| tab.is_number())
+        .map(|tab| tab.to_double() as u64)
+}
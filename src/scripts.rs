@@ -0,0 +1,144 @@
+//! Scripts injected into pages. Each one is a file under `templates/js/`
+//! compiled into the resource bundle. A file with the same name in the
+//! `scripts/` config directory replaces the bundled copy.
+
+use std::fs;
+
+use gtk4::gio;
+use webkit6::javascriptcore;
+
+use crate::{
+    messages::{self, Level},
+    paths,
+};
+
+/// The script world page scripts run in, apart from the page's own
+/// scripts so that pages can't tamper with them or their messages.
+pub const PAGE_SCRIPT_WORLD: &str = "tilde";
+
+pub struct PageScript {
+    pub name: &'static str,
+    /// Bumped whenever the script changes in a way overrides should follow.
+    pub version: u32,
+}
+
+/// Page scripts, in injection order.
+pub const SCRIPTS: &[PageScript] = &[
+    PageScript {
+        name: "edit_state",
+        version: 2,
+    },
+    PageScript {
+        name: "hints",
+        version: 3,
+    },
+    PageScript {
+        name: "blocked_count",
+        version: 1,
+    },
+    PageScript {
+        name: "scroll_state",
+        version: 1,
+    },
+];
+
+impl PageScript {
+    /// The user's copy of the script, if there is one. Overrides can state
+    /// the version they were written against with a `// @version N` line.
+    fn user_override(&self) -> Option<String> {
+        let path = paths::config_dir()
+            .join("scripts")
+            .join(format!("{}.js", self.name));
+        let source = fs::read_to_string(&path).ok()?;
+
+        if let Some(version) = declared_version(&source)
+            && version < self.version
+        {
+            messages::warning(&format!(
+                "{} was written for {} v{}, tilde now ships v{}",
+                path.display(),
+                self.name,
+                version,
+                self.version
+            ));
+        }
+
+        Some(source)
+    }
+
+    pub fn source(&self) -> Option<String> {
+        self.user_override().or_else(|| bundled(self.name))
+    }
+}
+
+/// The page scripts to inject, in order, each on its own so that one that
+/// fails doesn't stop the others. Each runs in a function scope of its own,
+/// after a preamble that records which versions are loaded in
+/// `window.__tildeScripts`.
+pub fn compose() -> Vec<String> {
+    let versions = SCRIPTS
+        .iter()
+        .map(|script| format!("{}: {}", script.name, script.version))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut composed = vec![format!("window.__tildeScripts = {{ {} }};\n", versions)];
+
+    for script in SCRIPTS {
+        match script.source() {
+            Some(source) => composed.push(format!(
+                "// {} v{}\n(function () {{\n{}\n}})();\n",
+                script.name, script.version, source
+            )),
+            None => messages::error(&format!("Missing page script: {}", script.name)),
+        }
+    }
+
+    composed
+}
+
+/// The copy of `templates/js/NAME.js` in the resource bundle.
+pub fn bundled(name: &str) -> Option<String> {
+    let path = format!("/templates/js/{}.js", name);
+    let bytes = gio::resources_lookup_data(&path, gio::ResourceLookupFlags::NONE).ok()?;
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+fn declared_version(source: &str) -> Option<u32> {
+    source
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("// @version"))
+        .and_then(|version| version.trim().parse().ok())
+}
+
+/// `s` as a JavaScript (and JSON) string literal.
+pub fn js_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{2028}' | '\u{2029}' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// A callback for `evaluate_javascript` that reports it if running `what`
+/// failed, at `level`.
+pub fn report_failure(
+    level: Level,
+    what: &'static str,
+) -> impl FnOnce(Result<javascriptcore::Value, glib::Error>) + 'static {
+    move |result| {
+        if let Err(err) = result {
+            messages::log(level, &format!("{} failed: {}", what, err));
+        }
+    }
+}
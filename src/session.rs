@@ -0,0 +1,142 @@
+use glib::{KeyFile, KeyFileFlags};
+
+use crate::{messages, paths};
+
+const SESSION_FILE: &str = "session";
+
+pub const DEFAULT_WORKSPACE: &str = "default";
+
+#[derive(Clone, Debug, Default)]
+pub struct TabState {
+    pub uri: String,
+    pub title: String,
+    pub pinned: bool,
+}
+
+/// A named set of tabs. Only the active workspace of a window has live
+/// WebViews; the others are kept in this form.
+#[derive(Clone, Debug, Default)]
+pub struct WorkspaceState {
+    pub name: String,
+    pub tabs: Vec<TabState>,
+    pub current: u32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct WindowState {
+    pub workspaces: Vec<WorkspaceState>,
+    pub active: usize,
+}
+
+impl WindowState {
+    pub fn is_empty(&self) -> bool {
+        self.workspaces.iter().all(|w| w.tabs.is_empty())
+    }
+}
+
+/// Open windows and their tabs, saved when tilde quits and restored on the
+/// next start.
+#[derive(Debug, Default)]
+pub struct Session {
+    pub windows: Vec<WindowState>,
+}
+
+impl Session {
+    pub fn load() -> Self {
+        let file = KeyFile::new();
+        if file
+            .load_from_file(paths::data_file(SESSION_FILE), KeyFileFlags::NONE)
+            .is_err()
+        {
+            return Self::default();
+        }
+
+        let mut windows = Vec::new();
+        while file.has_group(&window_group(windows.len())) {
+            let w = windows.len();
+            let group = window_group(w);
+            let n_workspaces = read_count(&file, &group, "workspaces");
+
+            let workspaces = (0..n_workspaces)
+                .map(|k| {
+                    let group = workspace_group(w, k);
+                    let n_tabs = read_count(&file, &group, "tabs");
+
+                    WorkspaceState {
+                        name: file
+                            .string(&group, "name")
+                            .map(|n| n.to_string())
+                            .unwrap_or_else(|_| DEFAULT_WORKSPACE.into()),
+                        tabs: (0..n_tabs)
+                            .filter_map(|t| read_tab(&file, &tab_group(w, k, t)))
+                            .collect(),
+                        current: read_count(&file, &group, "current") as u32,
+                    }
+                })
+                .collect();
+
+            windows.push(WindowState {
+                workspaces,
+                active: read_count(&file, &group, "active"),
+            });
+        }
+
+        Self { windows }
+    }
+
+    pub fn save(&self) {
+        let file = KeyFile::new();
+
+        // Windows without tabs are not worth restoring
+        for (w, window) in self.windows.iter().filter(|w| !w.is_empty()).enumerate() {
+            let group = window_group(w);
+            file.set_integer(&group, "workspaces", window.workspaces.len() as i32);
+            file.set_integer(&group, "active", window.active as i32);
+
+            for (k, workspace) in window.workspaces.iter().enumerate() {
+                let group = workspace_group(w, k);
+                file.set_string(&group, "name", &workspace.name);
+                file.set_integer(&group, "tabs", workspace.tabs.len() as i32);
+                file.set_integer(&group, "current", workspace.current as i32);
+
+                for (t, tab) in workspace.tabs.iter().enumerate() {
+                    let group = tab_group(w, k, t);
+                    file.set_string(&group, "uri", &tab.uri);
+                    file.set_string(&group, "title", &tab.title);
+                    file.set_boolean(&group, "pinned", tab.pinned);
+                }
+            }
+        }
+
+        if let Err(err) = paths::write_file(&paths::data_file(SESSION_FILE), &file.to_data()) {
+            messages::error(&format!("Failed to save session: {}", err));
+        }
+    }
+}
+
+fn read_count(file: &KeyFile, group: &str, key: &str) -> usize {
+    file.integer(group, key).unwrap_or(0).max(0) as usize
+}
+
+fn read_tab(file: &KeyFile, group: &str) -> Option<TabState> {
+    Some(TabState {
+        uri: file.string(group, "uri").ok()?.to_string(),
+        title: file
+            .string(group, "title")
+            .map(|t| t.to_string())
+            .unwrap_or_default(),
+        pinned: file.boolean(group, "pinned").unwrap_or(false),
+    })
+}
+
+fn window_group(w: usize) -> String {
+    format!("window {}", w)
+}
+
+fn workspace_group(w: usize, k: usize) -> String {
+    format!("window {} workspace {}", w, k)
+}
+
+fn tab_group(w: usize, k: usize, t: usize) -> String {
+    format!("window {} workspace {} tab {}", w, k, t)
+}
@@ -0,0 +1,468 @@
+//! Global settings. Every option is declared in `SETTINGS` with its type and
+//! default; values the user changed are kept in `settings.toml` in the
+//! config directory, where a dotted name is a key in a table, e.g.
+//!
+//! ```toml
+//! [hints]
+//! alphabet = "aoeuidhtns"
+//! ```
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt, fs, io,
+    path::PathBuf,
+    rc::Rc,
+};
+
+use crate::{messages, paths};
+
+const SETTINGS_FILE: &str = "settings.toml";
+
+/// Segments the right of the dock can show.
+pub const DOCK_SEGMENTS: &[&str] = &[
+    "progress", "security", "mode", "keys", "scroll", "zoom", "blocked", "tabs",
+];
+
+#[derive(Clone, Copy, Debug)]
+pub enum Kind {
+    Bool,
+    Int {
+        min: i64,
+        max: i64,
+    },
+    Text,
+    /// One of the given words
+    Choice(&'static [&'static str]),
+    /// Words separated by whitespace, each one of the choices if any given.
+    List(&'static [&'static str]),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Text(String),
+    List(Vec<String>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Text(value) => write!(f, "{}", value),
+            Value::List(values) => write!(f, "{}", values.join(" ")),
+        }
+    }
+}
+
+pub struct SettingInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub kind: Kind,
+    /// Text form of the default, as it would be given to `:set`.
+    pub default: &'static str,
+    /// Extra validation beyond what `kind` checks.
+    check: fn(&Value) -> Result<(), String>,
+}
+
+/// Every setting, in the order `tilde://settings` lists them.
+pub const SETTINGS: &[SettingInfo] = &[
+    SettingInfo {
+        name: "start.pages",
+        description: "Pages a new window opens with",
+        kind: Kind::List(&[]),
+        default: "https://duckduckgo.com",
+        check: no_check,
+    },
+    SettingInfo {
+        name: "start.new-tab-page",
+        description: "Page new tabs open with, e.g. tilde://start",
+        kind: Kind::Text,
+        default: "tilde://start",
+        check: no_check,
+    },
+    SettingInfo {
+        name: "search.engine",
+        description: "Search URL, with {} where the query goes",
+        kind: Kind::Text,
+        default: "https://duckduckgo.com/?q={}",
+        check: check_search_engine,
+    },
+    SettingInfo {
+        name: "scroll.step",
+        description: "Pixels j and k scroll by",
+        kind: Kind::Int { min: 1, max: 10000 },
+        default: "50",
+        check: no_check,
+    },
+    SettingInfo {
+        name: "zoom.text-only",
+        description: "Zoom only text, not images and layout",
+        kind: Kind::Bool,
+        default: "false",
+        check: no_check,
+    },
+    SettingInfo {
+        name: "dock.segments",
+        description: "What the right of the dock shows, in order. The blocked count is an estimate",
+        kind: Kind::List(DOCK_SEGMENTS),
+        default: "progress security mode keys scroll zoom blocked tabs",
+        check: no_check,
+    },
+    SettingInfo {
+        name: "dock.mode",
+        description: "When the dock shows: manual, always, auto-hide or flash",
+        kind: Kind::Choice(&["manual", "always", "auto-hide", "flash"]),
+        default: "manual",
+        check: no_check,
+    },
+    SettingInfo {
+        name: "dock.hide-after",
+        description: "Seconds the dock and its messages stay up when shown briefly",
+        kind: Kind::Int { min: 1, max: 60 },
+        default: "3",
+        check: no_check,
+    },
+    SettingInfo {
+        name: "dock.reveal-on-load",
+        description: "Show the dock while the current tab loads",
+        kind: Kind::Bool,
+        default: "false",
+        check: no_check,
+    },
+    SettingInfo {
+        name: "hints.alphabet",
+        description: "Characters link hint labels are made of",
+        kind: Kind::Text,
+        default: "asdfghjklqwertyuiopzxcvbnm",
+        check: check_alphabet,
+    },
+    SettingInfo {
+        name: "hints.uppercase",
+        description: "Show link hint labels in capitals",
+        kind: Kind::Bool,
+        default: "false",
+        check: no_check,
+    },
+    SettingInfo {
+        name: "log.level",
+        description: "Least important messages written to the log file: debug, info, warning, error or off",
+        kind: Kind::Choice(&["debug", "info", "warning", "error", "off"]),
+        default: "info",
+        check: no_check,
+    },
+    SettingInfo {
+        name: "suspend.after",
+        description: "Minutes a background tab may sit untouched before it is suspended, 0 for never",
+        kind: Kind::Int { min: 0, max: 1440 },
+        default: "30",
+        check: no_check,
+    },
+];
+
+fn no_check(_: &Value) -> Result<(), String> {
+    Ok(())
+}
+
+fn check_search_engine(value: &Value) -> Result<(), String> {
+    match value {
+        Value::Text(url) if url.contains("{}") => Ok(()),
+        _ => Err("The search URL needs a {} for the query".to_string()),
+    }
+}
+
+fn check_alphabet(value: &Value) -> Result<(), String> {
+    let Value::Text(alphabet) = value else {
+        return Ok(());
+    };
+    let mut chars: Vec<char> = alphabet.chars().collect();
+    chars.sort_unstable();
+    chars.dedup();
+
+    if chars.len() < 2 || chars.len() != alphabet.chars().count() {
+        return Err("Hint alphabets need at least two distinct characters".to_string());
+    }
+    if chars.iter().any(|ch| ch.is_whitespace()) {
+        return Err("Hint alphabets can't contain spaces".to_string());
+    }
+    Ok(())
+}
+
+impl SettingInfo {
+    pub fn default_value(&self) -> Value {
+        self.parse(self.default)
+            .expect("setting defaults are valid")
+    }
+
+    /// Reads the text form of a value, as typed after `:set NAME`.
+    pub fn parse(&self, text: &str) -> Result<Value, String> {
+        let text = text.trim();
+        let value = match self.kind {
+            Kind::Bool => match text {
+                "true" | "on" | "yes" => Value::Bool(true),
+                "false" | "off" | "no" => Value::Bool(false),
+                _ => return Err(format!("{} is true or false", self.name)),
+            },
+            Kind::Int { .. } => Value::Int(
+                text.parse()
+                    .map_err(|_| format!("{} is a whole number", self.name))?,
+            ),
+            Kind::Text | Kind::Choice(_) => Value::Text(text.to_string()),
+            Kind::List(_) => Value::List(text.split_whitespace().map(str::to_string).collect()),
+        };
+        self.validate(value)
+    }
+
+    fn validate(&self, value: Value) -> Result<Value, String> {
+        match (&self.kind, &value) {
+            (Kind::Int { min, max }, Value::Int(n)) if n < min || n > max => {
+                return Err(format!("{} is between {} and {}", self.name, min, max));
+            }
+            (Kind::Choice(choices), Value::Text(text)) if !choices.contains(&text.as_str()) => {
+                return Err(format!("{} is one of {}", self.name, choices.join(", ")));
+            }
+            (Kind::List(choices), Value::List(items)) if !choices.is_empty() => {
+                if let Some(item) = items.iter().find(|item| !choices.contains(&item.as_str())) {
+                    return Err(format!(
+                        "{} can't contain {}, only {}",
+                        self.name,
+                        item,
+                        choices.join(", ")
+                    ));
+                }
+            }
+            _ => {}
+        }
+        (self.check)(&value)?;
+        Ok(value)
+    }
+
+    fn value_from_toml(&self, value: &toml::Value) -> Result<Value, String> {
+        let value = match (&self.kind, value) {
+            (Kind::Bool, toml::Value::Boolean(b)) => Value::Bool(*b),
+            (Kind::Int { .. }, toml::Value::Integer(n)) => Value::Int(*n),
+            (Kind::Text | Kind::Choice(_), toml::Value::String(s)) => Value::Text(s.clone()),
+            (Kind::List(_), toml::Value::String(s)) => {
+                Value::List(s.split_whitespace().map(str::to_string).collect())
+            }
+            (Kind::List(_), toml::Value::Array(items)) => Value::List(
+                items
+                    .iter()
+                    .map(|item| item.as_str().map(str::to_string))
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| format!("{} is a list of strings", self.name))?,
+            ),
+            _ => return Err(format!("{} has the wrong type", self.name)),
+        };
+        self.validate(value)
+    }
+}
+
+fn to_toml(value: &Value) -> toml::Value {
+    match value {
+        Value::Bool(b) => toml::Value::Boolean(*b),
+        Value::Int(n) => toml::Value::Integer(*n),
+        Value::Text(s) => toml::Value::String(s.clone()),
+        Value::List(items) => {
+            toml::Value::Array(items.iter().cloned().map(toml::Value::String).collect())
+        }
+    }
+}
+
+pub fn info(name: &str) -> Option<&'static SettingInfo> {
+    SETTINGS.iter().find(|info| info.name == name)
+}
+
+type Listener = Rc<dyn Fn(&str)>;
+
+/// Identifies a listener added with `connect`, to remove it again.
+pub struct ListenerId(u64);
+
+thread_local! {
+    // Values the user changed from the default, by name
+    static VALUES: RefCell<Option<HashMap<&'static str, Value>>> = const { RefCell::new(None) };
+    static LISTENERS: RefCell<Vec<(u64, Listener)>> = const { RefCell::new(Vec::new()) };
+    static NEXT_LISTENER_ID: Cell<u64> = const { Cell::new(0) };
+}
+
+fn with_values<T>(f: impl FnOnce(&mut HashMap<&'static str, Value>) -> T) -> T {
+    let mut errors = Vec::new();
+    let result =
+        VALUES.with_borrow_mut(|values| f(values.get_or_insert_with(|| load(&mut errors))));
+
+    // Only now, as messages look at settings themselves
+    for err in errors {
+        messages::error(&err);
+    }
+    result
+}
+
+/// Where the user's settings are kept.
+pub fn path() -> PathBuf {
+    paths::config_dir().join(SETTINGS_FILE)
+}
+
+fn load(errors: &mut Vec<String>) -> HashMap<&'static str, Value> {
+    let path = path();
+    let Ok(contents) = fs::read_to_string(&path) else {
+        return HashMap::new();
+    };
+    let table: toml::Table = match contents.parse() {
+        Ok(table) => table,
+        Err(err) => {
+            errors.push(format!("{}: {}", path.display(), err));
+            return HashMap::new();
+        }
+    };
+
+    let mut values = HashMap::new();
+    for info in SETTINGS {
+        let Some(value) = lookup(&table, info.name) else {
+            continue;
+        };
+        match info.value_from_toml(value) {
+            Ok(value) => {
+                values.insert(info.name, value);
+            }
+            Err(err) => errors.push(format!("{}: {}", path.display(), err)),
+        }
+    }
+    values
+}
+
+/// The value at dotted `name` in `table`.
+fn lookup<'a>(table: &'a toml::Table, name: &str) -> Option<&'a toml::Value> {
+    let (tables, key) = match name.rsplit_once('.') {
+        Some((tables, key)) => (tables.split('.').collect(), key),
+        None => (Vec::new(), name),
+    };
+
+    let mut table = table;
+    for name in tables {
+        table = table.get(name)?.as_table()?;
+    }
+    table.get(key)
+}
+
+fn save(values: &HashMap<&'static str, Value>) -> io::Result<()> {
+    let mut root = toml::Table::new();
+
+    // In declaration order, so the file doesn't shuffle on every save
+    for info in SETTINGS {
+        let Some(value) = values.get(info.name) else {
+            continue;
+        };
+        let mut parts: Vec<&str> = info.name.split('.').collect();
+        let key = parts.pop().unwrap_or(info.name);
+
+        let mut table = &mut root;
+        for part in parts {
+            table = table
+                .entry(part)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                .as_table_mut()
+                .expect("setting names don't clash with tables");
+        }
+        table.insert(key.to_string(), to_toml(value));
+    }
+
+    paths::write_file(&path(), &root.to_string())
+}
+
+fn report_save(result: io::Result<()>) {
+    if let Err(err) = result {
+        messages::error(&format!("Failed to save settings: {}", err));
+    }
+}
+
+/// The current value of `name`. Panics for names not in `SETTINGS`.
+pub fn get(name: &str) -> Value {
+    let info = info(name).unwrap_or_else(|| panic!("unknown setting {}", name));
+    with_values(|values| values.get(name).cloned()).unwrap_or_else(|| info.default_value())
+}
+
+pub fn flag(name: &str) -> bool {
+    get(name) == Value::Bool(true)
+}
+
+pub fn int(name: &str) -> i64 {
+    match get(name) {
+        Value::Int(n) => n,
+        value => panic!("{} is not a number: {}", name, value),
+    }
+}
+
+pub fn text(name: &str) -> String {
+    match get(name) {
+        Value::Text(text) => text,
+        value => value.to_string(),
+    }
+}
+
+pub fn list(name: &str) -> Vec<String> {
+    match get(name) {
+        Value::List(items) => items,
+        value => vec![value.to_string()],
+    }
+}
+
+pub fn is_default(name: &str) -> bool {
+    with_values(|values| !values.contains_key(name))
+}
+
+/// Sets `name` from its text form, saves it and tells the listeners if
+/// the value changed.
+pub fn set(name: &str, text: &str) -> Result<(), String> {
+    let info = info(name).ok_or_else(|| format!("Unknown setting: {}", name))?;
+    let value = info.parse(text)?;
+    if value == get(name) {
+        return Ok(());
+    }
+
+    let saved = with_values(|values| {
+        if value == info.default_value() {
+            values.remove(info.name);
+        } else {
+            values.insert(info.name, value);
+        }
+        save(values)
+    });
+    report_save(saved);
+    notify(info.name);
+    Ok(())
+}
+
+/// Puts `name` back to its default.
+pub fn reset(name: &str) -> Result<(), String> {
+    let info = info(name).ok_or_else(|| format!("Unknown setting: {}", name))?;
+
+    let changed = with_values(|values| values.remove(info.name).is_some());
+    if changed {
+        report_save(with_values(|values| save(values)));
+        notify(info.name);
+    }
+    Ok(())
+}
+
+/// Calls `f` with the name of every setting that changes from now on, until
+/// `disconnect` is called with the returned id.
+pub fn connect(f: impl Fn(&str) + 'static) -> ListenerId {
+    let id = NEXT_LISTENER_ID.get();
+    NEXT_LISTENER_ID.set(id + 1);
+    LISTENERS.with_borrow_mut(|listeners| listeners.push((id, Rc::new(f))));
+    ListenerId(id)
+}
+
+pub fn disconnect(id: ListenerId) {
+    LISTENERS.with_borrow_mut(|listeners| listeners.retain(|(other, _)| *other != id.0));
+}
+
+fn notify(name: &str) {
+    // Listeners may read settings or add listeners of their own
+    let listeners = LISTENERS.with_borrow(|listeners| listeners.clone());
+    for (_, listener) in listeners {
+        listener(name);
+    }
+}
@@ -0,0 +1,395 @@
+//! Per-site settings. Rules in the `sites` config file match pages by URL
+//! pattern and override settings for the tabs showing them. Each group of
+//! the file is a pattern and each key a setting:
+//!
+//! ```text
+//! [*.example.com]
+//! javascript=false
+//! zoom=1.25
+//! ```
+//!
+//! A pattern without a `/` matches the host, with `*.example.com` also
+//! matching `example.com` itself; anything else matches the whole URL.
+//! `*` stands for any run of characters. Later rules win.
+//!
+//! WebKit has one cookie policy per network session, not per site, so
+//! `cookies` rules are carried out by the content filter instead: it drops
+//! cookies from the requests and responses of matching pages. They only
+//! take host patterns, and `always` can only lift tilde's own third-party
+//! rule, not cookie blocking a site does itself.
+
+use std::cell::RefCell;
+
+use glib::{KeyFile, KeyFileFlags};
+
+use crate::{messages, paths, scripts::js_string};
+
+const SITES_FILE: &str = "sites";
+
+/// Settings a rule can override, with a short description.
+pub const KEYS: &[(&str, &str)] = &[
+    ("javascript", "Run page scripts (true/false)"),
+    ("images", "Load images (true/false)"),
+    ("autoplay", "Play media without a click (true/false)"),
+    ("user-agent", "User agent string sent to the site"),
+    ("zoom", "Page zoom level, e.g. 1.25"),
+    ("font-size", "Default font size in pixels"),
+    ("dark-mode", "Invert the page's colours (true/false)"),
+    (
+        "cookies",
+        "Cookies to accept on a host (always/no-third-party/never)",
+    ),
+    ("passthrough", "Send every key to the page (true/false)"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CookiePolicy {
+    Always,
+    NoThirdParty,
+    Never,
+}
+
+impl CookiePolicy {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "always" => Some(Self::Always),
+            "no-third-party" => Some(Self::NoThirdParty),
+            "never" => Some(Self::Never),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Always => "always",
+            Self::NoThirdParty => "no-third-party",
+            Self::Never => "never",
+        }
+    }
+}
+
+/// Overrides for one page. Unset fields keep the default.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SiteSettings {
+    pub javascript: Option<bool>,
+    pub images: Option<bool>,
+    pub autoplay: Option<bool>,
+    pub user_agent: Option<String>,
+    pub zoom: Option<f64>,
+    pub font_size: Option<u32>,
+    pub dark_mode: Option<bool>,
+    pub cookies: Option<CookiePolicy>,
+    pub passthrough: Option<bool>,
+}
+
+impl SiteSettings {
+    /// Sets `key` from its text form, or unsets it for `default`.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = Some(value.trim()).filter(|value| *value != "default");
+
+        match key {
+            "javascript" => self.javascript = value.map(parse_bool).transpose()?,
+            "images" => self.images = value.map(parse_bool).transpose()?,
+            "autoplay" => self.autoplay = value.map(parse_bool).transpose()?,
+            "user-agent" => self.user_agent = value.map(str::to_string),
+            "zoom" => {
+                self.zoom = value
+                    .map(|value| match value.parse::<f64>() {
+                        Ok(zoom) if zoom > 0.0 => Ok(zoom),
+                        _ => Err(format!("Not a zoom level: {}", value)),
+                    })
+                    .transpose()?
+            }
+            "font-size" => {
+                self.font_size = value
+                    .map(|value| match value.parse::<u32>() {
+                        Ok(size) if size > 0 => Ok(size),
+                        _ => Err(format!("Not a font size: {}", value)),
+                    })
+                    .transpose()?
+            }
+            "dark-mode" => self.dark_mode = value.map(parse_bool).transpose()?,
+            "cookies" => {
+                self.cookies = value
+                    .map(|value| {
+                        CookiePolicy::from_name(value)
+                            .ok_or_else(|| format!("Not a cookie policy: {}", value))
+                    })
+                    .transpose()?
+            }
+            "passthrough" => self.passthrough = value.map(parse_bool).transpose()?,
+            _ => return Err(format!("Unknown site setting: {}", key)),
+        }
+        Ok(())
+    }
+
+    /// The set fields as key/value text, in `KEYS` order.
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        let values = [
+            self.javascript.map(|v| v.to_string()),
+            self.images.map(|v| v.to_string()),
+            self.autoplay.map(|v| v.to_string()),
+            self.user_agent.clone(),
+            self.zoom.map(|v| v.to_string()),
+            self.font_size.map(|v| v.to_string()),
+            self.dark_mode.map(|v| v.to_string()),
+            self.cookies.map(|v| v.name().to_string()),
+            self.passthrough.map(|v| v.to_string()),
+        ];
+
+        KEYS.iter()
+            .zip(values)
+            .filter_map(|((key, _), value)| Some((*key, value?)))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Overrides this with every field `other` sets.
+    fn merge(&mut self, other: &SiteSettings) {
+        for (key, value) in other.entries() {
+            // Values came out of `entries`, so they parse
+            let _ = self.set(key, &value);
+        }
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "on" | "yes" => Ok(true),
+        "false" | "off" | "no" => Ok(false),
+        _ => Err(format!("Not true or false: {}", value)),
+    }
+}
+
+struct Rule {
+    pattern: String,
+    settings: SiteSettings,
+}
+
+thread_local! {
+    static RULES: RefCell<Option<Vec<Rule>>> = const { RefCell::new(None) };
+}
+
+fn with_rules<T>(f: impl FnOnce(&mut Vec<Rule>) -> T) -> T {
+    RULES.with_borrow_mut(|rules| f(rules.get_or_insert_with(load)))
+}
+
+fn load() -> Vec<Rule> {
+    let file = KeyFile::new();
+    if file
+        .load_from_file(paths::config_dir().join(SITES_FILE), KeyFileFlags::NONE)
+        .is_err()
+    {
+        return Vec::new();
+    }
+
+    file.groups()
+        .iter()
+        .map(|pattern| {
+            let mut settings = SiteSettings::default();
+            for key in file.keys(pattern).unwrap_or_default().iter() {
+                let value = file.string(pattern, key).unwrap_or_default();
+                if let Err(err) = settings.set(key, &value) {
+                    messages::warning(&format!("{} [{}]: {}", SITES_FILE, pattern, err));
+                }
+            }
+            Rule {
+                pattern: pattern.to_string(),
+                settings,
+            }
+        })
+        .collect()
+}
+
+fn save(rules: &[Rule]) {
+    let file = KeyFile::new();
+    for rule in rules {
+        for (key, value) in rule.settings.entries() {
+            file.set_string(&rule.pattern, key, &value);
+        }
+    }
+
+    let path = paths::config_dir().join(SITES_FILE);
+    if let Err(err) = paths::write_file(&path, &file.to_data()) {
+        messages::error(&format!("Failed to save site settings: {}", err));
+    }
+}
+
+/// The settings for the page at `uri`, from every rule that matches it.
+pub fn for_uri(uri: &str) -> SiteSettings {
+    let host = glib::Uri::parse(uri, glib::UriFlags::NONE)
+        .ok()
+        .and_then(|uri| uri.host())
+        .map(|host| host.to_string())
+        .unwrap_or_default();
+
+    with_rules(|rules| {
+        let mut settings = SiteSettings::default();
+        for rule in rules.iter() {
+            if matches(&rule.pattern, uri, &host) {
+                settings.merge(&rule.settings);
+            }
+        }
+        settings
+    })
+}
+
+/// The settings the rule for exactly `pattern` sets.
+pub fn rule(pattern: &str) -> SiteSettings {
+    with_rules(|rules| {
+        rules
+            .iter()
+            .find(|rule| rule.pattern == pattern)
+            .map(|rule| rule.settings.clone())
+            .unwrap_or_default()
+    })
+}
+
+/// Sets `key` for pages matching `pattern` and saves the rules. A value
+/// of `default` drops the override.
+pub fn set(pattern: &str, key: &str, value: &str) -> Result<(), String> {
+    if pattern.is_empty() || pattern.contains(['[', ']']) {
+        return Err(format!("Not a site pattern: {}", pattern));
+    }
+
+    if key == "cookies" && value.trim() != "default" && content_rule_domain(pattern).is_none() {
+        return Err(format!(
+            "Cookie rules need a host pattern, like *.example.com: {}",
+            pattern
+        ));
+    }
+
+    with_rules(|rules| {
+        let index = match rules.iter().position(|rule| rule.pattern == pattern) {
+            Some(index) => index,
+            None => {
+                rules.push(Rule {
+                    pattern: pattern.to_string(),
+                    settings: SiteSettings::default(),
+                });
+                rules.len() - 1
+            }
+        };
+
+        let result = rules[index].settings.set(key, value);
+        if rules[index].settings.is_empty() {
+            rules.remove(index);
+        }
+        if result.is_ok() {
+            save(rules);
+        }
+        result
+    })
+}
+
+/// The patterns of all rules, in file order.
+pub fn patterns() -> Vec<String> {
+    with_rules(|rules| rules.iter().map(|rule| rule.pattern.clone()).collect())
+}
+
+/// Content filter rules, as JSON objects, that carry out the `cookies`
+/// rules: third-party cookies are blocked except on `always` sites, and
+/// every cookie of pages on `never` sites. They go last in the filter, so
+/// that turning adblock off for a site leaves them be.
+pub fn cookie_rules() -> Vec<String> {
+    let mut always: Vec<String> = Vec::new();
+    let mut never: Vec<String> = Vec::new();
+
+    with_rules(|rules| {
+        for rule in rules.iter() {
+            let Some(policy) = rule.settings.cookies else {
+                continue;
+            };
+            let Some(domain) = content_rule_domain(&rule.pattern) else {
+                messages::warning(&format!(
+                    "{} [{}]: cookies need a host pattern",
+                    SITES_FILE, rule.pattern
+                ));
+                continue;
+            };
+
+            always.retain(|other| *other != domain);
+            never.retain(|other| *other != domain);
+            match policy {
+                CookiePolicy::Always => always.push(domain),
+                CookiePolicy::Never => never.push(domain),
+                CookiePolicy::NoThirdParty => {}
+            }
+        }
+    });
+
+    let list = |domains: &[String]| {
+        domains
+            .iter()
+            .map(|domain| js_string(domain))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let unless = if always.is_empty() {
+        String::new()
+    } else {
+        format!(",\"unless-domain\":[{}]", list(&always))
+    };
+
+    let mut json = vec![format!(
+        "{{\"trigger\":{{\"url-filter\":\".*\",\"load-type\":[\"third-party\"]{}}},\"action\":{{\"type\":\"block-cookies\"}}}}",
+        unless
+    )];
+    if !never.is_empty() {
+        json.push(format!(
+            "{{\"trigger\":{{\"url-filter\":\".*\",\"if-domain\":[{}]}},\"action\":{{\"type\":\"block-cookies\"}}}}",
+            list(&never)
+        ));
+    }
+    json
+}
+
+/// `pattern` in the form content filters match the page's domain with, if
+/// it is a host or a host with its subdomains.
+fn content_rule_domain(pattern: &str) -> Option<String> {
+    let subdomains = pattern.strip_prefix("*.");
+    let host = subdomains.unwrap_or(pattern).to_lowercase();
+    let valid = !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+
+    valid.then(|| match subdomains {
+        Some(_) => format!("*{}", host),
+        None => host,
+    })
+}
+
+fn matches(pattern: &str, uri: &str, host: &str) -> bool {
+    if pattern.contains('/') {
+        return glob(pattern, uri);
+    }
+    pattern.strip_prefix("*.") == Some(host) || glob(pattern, host)
+}
+
+/// Whether `text` matches `pattern` in full, with `*` matching any run of
+/// characters.
+fn glob(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*` at all
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
@@ -0,0 +1,148 @@
+//! User stylesheets from the `stylesheets/` config directory. `global.css`
+//! applies to every page and `DOMAIN.css` (e.g. `example.com.css`) to that
+//! domain and its subdomains. Edits are picked up as soon as a file is saved.
+
+use std::{cell::RefCell, fs};
+
+use gtk4::gio::{self, prelude::FileExt as _, prelude::FileMonitorExt as _};
+use webkit6::{UserContentInjectedFrames, UserStyleLevel, UserStyleSheet};
+
+use crate::{messages, paths};
+
+const GLOBAL: &str = "global";
+
+const DISABLED_FILE: &str = "stylesheets-disabled";
+
+// In the disabled list, turns every stylesheet off at once
+const ALL: &str = "*";
+
+thread_local! {
+    static MONITOR: RefCell<Option<gio::FileMonitor>> = const { RefCell::new(None) };
+}
+
+pub struct Stylesheet {
+    /// File name without `.css`: `global` or a domain.
+    pub name: String,
+    pub enabled: bool,
+    source: String,
+}
+
+impl Stylesheet {
+    pub fn user_style_sheet(&self) -> UserStyleSheet {
+        let allow = if self.name == GLOBAL {
+            Vec::new()
+        } else {
+            vec![
+                format!("*://{}/*", self.name),
+                format!("*://*.{}/*", self.name),
+            ]
+        };
+        let allow: Vec<&str> = allow.iter().map(String::as_str).collect();
+
+        UserStyleSheet::new(
+            &self.source,
+            UserContentInjectedFrames::AllFrames,
+            UserStyleLevel::User,
+            &allow,
+            &[],
+        )
+    }
+}
+
+fn dir() -> std::path::PathBuf {
+    paths::config_dir().join("stylesheets")
+}
+
+/// Every stylesheet in the config directory, sorted by name.
+pub fn load_all() -> Vec<Stylesheet> {
+    let Ok(entries) = fs::read_dir(dir()) else {
+        return Vec::new();
+    };
+    let disabled = paths::read_list(DISABLED_FILE);
+    let all_disabled = disabled.iter().any(|name| name == ALL);
+
+    let mut sheets: Vec<Stylesheet> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            let name = path
+                .file_name()?
+                .to_str()?
+                .strip_suffix(".css")?
+                .to_string();
+            let source = fs::read_to_string(&path)
+                .inspect_err(|err| {
+                    messages::error(&format!("Failed to read {}: {}", path.display(), err))
+                })
+                .ok()?;
+
+            Some(Stylesheet {
+                enabled: !all_disabled && !disabled.contains(&name),
+                name,
+                source,
+            })
+        })
+        .collect();
+
+    sheets.sort_by(|a, b| a.name.cmp(&b.name));
+    sheets
+}
+
+/// Turns the stylesheet `name` on or off, or all of them when `name` is
+/// empty. Returns false if there is no such stylesheet.
+pub fn toggle(name: &str) -> bool {
+    let name = if name.is_empty() { ALL } else { name };
+    if name != ALL && !load_all().iter().any(|sheet| sheet.name == name) {
+        return false;
+    }
+
+    let mut disabled = paths::read_list(DISABLED_FILE);
+    let was_disabled = disabled.iter().any(|other| other == name);
+    disabled.retain(|other| other != name);
+    if !was_disabled {
+        disabled.push(name.to_string());
+    }
+
+    if let Err(err) = paths::write_list(DISABLED_FILE, &disabled) {
+        messages::error(&format!("Failed to save stylesheet state: {}", err));
+    }
+    true
+}
+
+pub fn all_disabled() -> bool {
+    paths::read_list(DISABLED_FILE)
+        .iter()
+        .any(|name| name == ALL)
+}
+
+/// Calls `on_change` whenever a file in the stylesheet directory changes.
+/// Only the first call sets up the watch.
+pub fn watch(on_change: impl Fn() + 'static) {
+    MONITOR.with_borrow_mut(|monitor| {
+        if monitor.is_some() {
+            return;
+        }
+
+        let dir = dir();
+        if let Err(err) = fs::create_dir_all(&dir) {
+            messages::error(&format!("Failed to create {}: {}", dir.display(), err));
+            return;
+        }
+
+        match gio::File::for_path(&dir).monitor_directory(
+            gio::FileMonitorFlags::WATCH_MOVES,
+            None::<&gio::Cancellable>,
+        ) {
+            Ok(file_monitor) => {
+                file_monitor.connect_changed(move |_, _, _, event| {
+                    // A save shows up as several Changed events and one hint
+                    if event != gio::FileMonitorEvent::Changed {
+                        on_change();
+                    }
+                });
+                *monitor = Some(file_monitor);
+            }
+            Err(err) => messages::error(&format!("Failed to watch {}: {}", dir.display(), err)),
+        }
+    });
+}
@@ -0,0 +1,355 @@
+//! Greasemonkey-style userscripts: `*.user.js` files in the `userscripts/`
+//! config directory, injected into matching pages along with a small `GM_*`
+//! API. Each runs in a script world of its own, out of reach of page scripts
+//! and of the other userscripts.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use webkit6::{UserContentInjectedFrames, UserScript, UserScriptInjectionTime};
+
+use crate::{
+    messages, paths,
+    scripts::{self, js_string},
+};
+
+const DISABLED_FILE: &str = "userscripts-disabled";
+
+// Message handlers of each userscript: GM_* calls, and its stored values
+pub const GM_HANDLER: &str = "gm";
+pub const VALUES_HANDLER: &str = "gmValues";
+
+// What each userscript can destructure from the API makeGM builds
+const GM_NAMES: &[&str] = &[
+    "GM_info",
+    "GM",
+    "GM_getValue",
+    "GM_setValue",
+    "GM_deleteValue",
+    "GM_listValues",
+    "GM_log",
+    "GM_addStyle",
+    "GM_openInTab",
+    "GM_setClipboard",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RunAt {
+    Start,
+    End,
+}
+
+#[derive(Clone, Debug)]
+pub struct Userscript {
+    /// File name without `.user.js`, unique within the directory.
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub version: String,
+    pub matches: Vec<String>,
+    pub excludes: Vec<String>,
+    pub run_at: RunAt,
+    pub grants: Vec<String>,
+    pub no_frames: bool,
+    pub enabled: bool,
+    source: String,
+}
+
+impl Userscript {
+    /// Reads the `==UserScript==` header of `source`. Scripts without one
+    /// are rejected.
+    pub fn parse(id: &str, source: &str) -> Result<Self, String> {
+        let mut script = Userscript {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: String::new(),
+            version: String::new(),
+            matches: Vec::new(),
+            excludes: Vec::new(),
+            run_at: RunAt::End,
+            grants: Vec::new(),
+            no_frames: false,
+            enabled: true,
+            source: source.to_string(),
+        };
+
+        let mut in_header = false;
+        let mut had_header = false;
+        let mut had_includes = false;
+
+        for line in source.lines() {
+            let Some(comment) = line.trim().strip_prefix("//") else {
+                continue;
+            };
+            let comment = comment.trim();
+
+            match comment {
+                "==UserScript==" => in_header = true,
+                "==/UserScript==" => {
+                    had_header = in_header;
+                    break;
+                }
+                _ if in_header => {
+                    let Some(meta) = comment.strip_prefix('@') else {
+                        continue;
+                    };
+                    let (key, value) = meta
+                        .split_once(char::is_whitespace)
+                        .map(|(key, value)| (key, value.trim()))
+                        .unwrap_or((meta, ""));
+
+                    match key {
+                        "name" => script.name = value.to_string(),
+                        "description" => script.description = value.to_string(),
+                        "version" => script.version = value.to_string(),
+                        "match" | "include" => {
+                            had_includes = true;
+                            script.matches.extend(url_pattern(value));
+                        }
+                        // Running where the script asked not to is worse
+                        // than not running it at all
+                        "exclude" | "exclude-match" => match url_pattern(value) {
+                            Some(pattern) => script.excludes.push(pattern),
+                            None => {
+                                return Err(format!(
+                                    "its @exclude pattern {} is not supported",
+                                    value
+                                ));
+                            }
+                        },
+                        "run-at" => {
+                            script.run_at = match value {
+                                "document-start" => RunAt::Start,
+                                _ => RunAt::End,
+                            }
+                        }
+                        "grant" if value != "none" => script.grants.push(value.to_string()),
+                        "noframes" => script.no_frames = true,
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if !had_header {
+            return Err("no ==UserScript== header".into());
+        }
+        // An empty allow list means every page, which is not what a script
+        // whose @include lines we could not translate asked for
+        if had_includes && script.matches.is_empty() {
+            return Err("none of its @match/@include patterns are supported".into());
+        }
+
+        Ok(script)
+    }
+
+    /// The script ready to add to a content manager, wrapped so it gets its
+    /// own copy of the GM API and the values it has stored. Scripts that run
+    /// at document-start do so before the page's scripts, but until tilde
+    /// answers, GM_getValue gives what was stored when they were installed.
+    pub fn user_script(&self) -> Option<UserScript> {
+        let api = scripts::bundled("gm_api")?;
+        let info = format!(
+            "{{ script: {{ id: {}, name: {}, description: {}, version: {} }}, scriptHandler: \"tilde\" }}",
+            js_string(&self.id),
+            js_string(&self.name),
+            js_string(&self.description),
+            js_string(&self.version),
+        );
+        let grants = self
+            .grants
+            .iter()
+            .map(|grant| js_string(grant))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        // Scripts wait for their stored values, except at document-start,
+        // where they make do with the values stored now until those arrive
+        let snapshot = match self.run_at {
+            RunAt::Start => values_json(&self.id),
+            RunAt::End => "null".to_string(),
+        };
+        let wrapped = format!(
+            "(function () {{\n{}\nconst handlers = window.webkit.messageHandlers;\nstartGM({}, [{}], {{ gm: handlers[{}], values: handlers[{}] }}, {}, function ({{ {} }}) {{\n{}\n}});\n}})();\n",
+            api,
+            info,
+            grants,
+            js_string(&handler_name(GM_HANDLER, &self.id)),
+            js_string(&handler_name(VALUES_HANDLER, &self.id)),
+            snapshot,
+            GM_NAMES.join(", "),
+            self.source,
+        );
+
+        let matches: Vec<&str> = self.matches.iter().map(String::as_str).collect();
+        let excludes: Vec<&str> = self.excludes.iter().map(String::as_str).collect();
+
+        Some(UserScript::for_world(
+            &wrapped,
+            if self.no_frames {
+                UserContentInjectedFrames::TopFrame
+            } else {
+                UserContentInjectedFrames::AllFrames
+            },
+            match self.run_at {
+                RunAt::Start => UserScriptInjectionTime::Start,
+                RunAt::End => UserScriptInjectionTime::End,
+            },
+            &world(&self.id),
+            &matches,
+            &excludes,
+        ))
+    }
+}
+
+/// The script world userscript `id` runs in.
+pub fn world(id: &str) -> String {
+    format!("userscript-{}", id)
+}
+
+/// The name of message handler `handler` of userscript `id`. Every script
+/// has handlers of its own, registered in its world only, so that tilde
+/// knows which script a message came from.
+pub fn handler_name(handler: &str, id: &str) -> String {
+    format!("{}-{}", handler, id)
+}
+
+/// Every userscript in the config directory, sorted by id. Scripts that
+/// fail to parse are reported and skipped.
+pub fn load_all() -> Vec<Userscript> {
+    let dir = paths::config_dir().join("userscripts");
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+    let disabled = paths::read_list(DISABLED_FILE);
+
+    let mut scripts: Vec<Userscript> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            let id = path
+                .file_name()?
+                .to_str()?
+                .strip_suffix(".user.js")?
+                .to_string();
+            load_file(&path, &id)
+        })
+        .map(|mut script| {
+            script.enabled = !disabled.contains(&script.id);
+            script
+        })
+        .collect();
+
+    scripts.sort_by(|a, b| a.id.cmp(&b.id));
+    scripts
+}
+
+fn load_file(path: &Path, id: &str) -> Option<Userscript> {
+    let source = fs::read_to_string(path)
+        .inspect_err(|err| messages::error(&format!("Failed to read {}: {}", path.display(), err)))
+        .ok()?;
+
+    Userscript::parse(id, &source)
+        .inspect_err(|err| {
+            messages::warning(&format!("Skipping userscript {}: {}", path.display(), err))
+        })
+        .ok()
+}
+
+pub fn set_enabled(id: &str, enabled: bool) {
+    let mut disabled = paths::read_list(DISABLED_FILE);
+    disabled.retain(|other| other != id);
+    if !enabled {
+        disabled.push(id.to_string());
+    }
+
+    if let Err(err) = paths::write_list(DISABLED_FILE, &disabled) {
+        messages::error(&format!("Failed to save userscript state: {}", err));
+    }
+}
+
+fn values_file(id: &str) -> std::path::PathBuf {
+    paths::data_dir()
+        .join("userscripts")
+        .join(format!("{}.values", id))
+}
+
+/// Stored `GM_setValue` values of script `id`: raw key -> JSON value. On disk
+/// each line holds the key as a JSON string, a tab, and the JSON value.
+fn read_values(id: &str) -> BTreeMap<String, String> {
+    let Ok(contents) = fs::read_to_string(values_file(id)) else {
+        return BTreeMap::new();
+    };
+
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once('\t')?;
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+fn write_values(id: &str, values: &BTreeMap<String, String>) {
+    let contents: String = values
+        .iter()
+        .map(|(key, value)| format!("{}\t{}\n", key, value))
+        .collect();
+
+    if let Err(err) = paths::write_file(&values_file(id), &contents) {
+        messages::error(&format!(
+            "Failed to save values of userscript {}: {}",
+            id, err
+        ));
+    }
+}
+
+/// `value` is already JSON, as produced by `JSON.stringify` in the page.
+pub fn set_value(id: &str, key: &str, value: &str) {
+    let mut values = read_values(id);
+    values.insert(js_string(key), value.to_string());
+    write_values(id, &values);
+}
+
+pub fn delete_value(id: &str, key: &str) {
+    let mut values = read_values(id);
+    values.remove(&js_string(key));
+    write_values(id, &values);
+}
+
+/// The stored values of script `id` as a JSON object.
+pub fn values_json(id: &str) -> String {
+    let entries = read_values(id)
+        .iter()
+        .map(|(key, value)| format!("{}: {}", key, value))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{{ {} }}", entries)
+}
+
+/// Translates a `@match` or `@include` pattern into WebKit's
+/// `scheme://host/path` form. Regular-expression includes and hosts with
+/// wildcards other than a leading `*.` are not supported.
+fn url_pattern(pattern: &str) -> Option<String> {
+    if pattern == "*" || pattern == "<all_urls>" {
+        return Some("*://*/*".into());
+    }
+
+    let (scheme, rest) = pattern.split_once("://")?;
+    let scheme = match scheme {
+        "http*" | "*" => "*",
+        "http" | "https" | "file" | "ftp" => scheme,
+        _ => return None,
+    };
+
+    let (host, path) = match rest.find('/') {
+        Some(idx) => rest.split_at(idx),
+        None => (rest, "/*"),
+    };
+    let host_ok = host == "*" || !host.strip_prefix("*.").unwrap_or(host).contains(['*', '/']);
+    if !host_ok || (host.is_empty() && scheme != "file") {
+        return None;
+    }
+
+    Some(format!("{}://{}{}", scheme, host, path))
+}
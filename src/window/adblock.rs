@@ -0,0 +1,128 @@
+use glib::object::ObjectExt as _;
+use gtk4::gio;
+use webkit6::{LoadEvent, WebView, prelude::WebViewExt as _};
+
+use super::{Window, window_of};
+use crate::{adblock, messages, profile, scripts, settings};
+
+pub(super) fn page_host(webview: &WebView) -> Option<String> {
+    let uri = webview.uri()?;
+    let uri = glib::Uri::parse(&uri, glib::UriFlags::NONE).ok()?;
+    uri.host().map(|host| host.to_string())
+}
+
+fn blocked_count(webview: &WebView) -> u32 {
+    unsafe {
+        webview
+            .data::<u32>("blocked_count")
+            .map_or(0, |count| *count.as_ptr())
+    }
+}
+
+/// Records how many requests the page in `webview` had blocked so far, as
+/// estimated by the page script: WebKit doesn't report blocked loads.
+pub fn show_blocked_count(webview: &WebView, blocked: u32) {
+    unsafe { webview.set_data("blocked_count", blocked) }
+
+    if let Some(window) = window_of(webview) {
+        window.update_blocked_label();
+    }
+}
+
+impl Window {
+    /// Starts each page of `webview` with a blocked count of zero.
+    pub(super) fn track_blocked_count(&self, webview: &WebView) {
+        webview.connect_load_changed(|webview, event| {
+            if event == LoadEvent::Committed {
+                show_blocked_count(webview, 0);
+            }
+        });
+    }
+
+    pub(super) fn update_blocked_label(&self) {
+        let Some(webview) = self.current_webview() else {
+            self.show_segment("blocked", "");
+            return;
+        };
+
+        let text = match page_host(&webview) {
+            Some(host) if adblock::is_disabled_for(&host) => "adblock off".to_string(),
+            _ => match blocked_count(&webview) {
+                0 => String::new(),
+                // An estimate, hence the tilde
+                count => format!("~{} blocked", count),
+            },
+        };
+
+        self.show_segment("blocked", &text);
+    }
+
+    /// `:adblock-toggle` turns content blocking off for the current site,
+    /// or back on, and reloads the page once the filter is rebuilt.
+    pub(super) fn toggle_adblock(&self) {
+        let Some(webview) = self.current_webview() else {
+            return;
+        };
+        let Some(host) = page_host(&webview) else {
+            messages::error("Content blocking applies to web pages only");
+            return;
+        };
+
+        let enabled = adblock::toggle_site(&host);
+        messages::info(&format!(
+            "Content blocking {} for {}",
+            if enabled { "enabled" } else { "disabled" },
+            host
+        ));
+        self.update_blocked_label();
+
+        adblock::compile(move |filter| {
+            profile::install_filter(&filter);
+            webview.reload();
+        });
+    }
+
+    /// `:hide-element` labels the elements on the page like link hints and
+    /// lets the user pick one by its label, hides it and saves a hiding rule
+    /// so it stays hidden on this site.
+    pub(super) fn hide_element(&self) {
+        let Some(webview) = self.current_webview() else {
+            return;
+        };
+        let Some(host) = page_host(&webview) else {
+            messages::error("Elements can be hidden on web pages only");
+            return;
+        };
+        let Some(picker) = scripts::bundled("element_picker") else {
+            messages::error("Element picker script is missing");
+            return;
+        };
+
+        let arguments = glib::VariantDict::new(None);
+        arguments.insert("alphabet", settings::text("hints.alphabet"));
+        arguments.insert("uppercase", settings::flag("hints.uppercase"));
+
+        // In the page scripts' world, where the hint engine is
+        webview.call_async_javascript_function(
+            &picker,
+            Some(&arguments.end()),
+            Some(scripts::PAGE_SCRIPT_WORLD),
+            None,
+            None::<&gio::Cancellable>,
+            move |result| {
+                let selector = match result {
+                    Ok(value) if value.is_string() => value.to_str().to_string(),
+                    Ok(_) => return,
+                    Err(err) => {
+                        messages::error(&format!("Element picker failed: {}", err));
+                        return;
+                    }
+                };
+
+                adblock::add_hiding_rule(&host, &selector);
+                messages::info(&format!("Hiding {} on {}", selector, host));
+                adblock::compile(|filter| profile::install_filter(&filter));
+            },
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use glib::object::ObjectExt as _;
+use gtk4::{
+    Application,
+    gio::{
+        self,
+        prelude::{ApplicationExt as _, FileExt as _},
+    },
+    prelude::GtkWindowExt as _,
+};
+use webkit6::{LoadEvent, prelude::WebViewExt as _};
+
+use super::Window;
+use crate::{messages, paths};
+
+const DEFAULT_TAB_COUNT: u32 = 100;
+const BENCHMARK_ENV: &str = "TILDE_BENCHMARK_TABS";
+// A page that hasn't loaded by then is counted as timed out
+const PAGE_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl Window {
+    /// `:benchmark-tabs [N]` opens N small local pages in background tabs and
+    /// reports how long creating the tabs took, and how long until every page
+    /// had finished loading.
+    pub(super) fn benchmark_tabs(&self, arg: &str) {
+        let Some(count) = benchmark_count(arg) else {
+            return;
+        };
+        match benchmark_pages(count) {
+            Ok(uris) => self.time_tabs(&uris, |line| messages::info(&line), || {}),
+            Err(err) => messages::error(&format!("Failed to write benchmark pages: {}", err)),
+        }
+    }
+
+    /// Runs the tab benchmark if `TILDE_BENCHMARK_TABS` asks for it, in a
+    /// window of its own that opens no start pages, prints the results and
+    /// quits. Returns whether it did. This is how
+    /// `scripts/benchmark-tabs.sh` drives the browser.
+    pub fn run_benchmark_from_env(app: &Application) -> bool {
+        let Ok(arg) = std::env::var(BENCHMARK_ENV) else {
+            return false;
+        };
+        let Some(count) = benchmark_count(&arg) else {
+            return false;
+        };
+
+        let uris = match benchmark_pages(count) {
+            Ok(uris) => uris,
+            Err(err) => {
+                eprintln!("Failed to write benchmark pages: {}", err);
+                return false;
+            }
+        };
+
+        let window = Self::new_empty(app);
+        let app = app.clone();
+        window.time_tabs(&uris, |line| println!("{}", line), move || app.quit());
+        window.present();
+        true
+    }
+
+    /// Opens `uris` in background tabs, passing each result line to
+    /// `report` and calling `on_loaded` once every page has loaded or timed
+    /// out.
+    fn time_tabs(
+        &self,
+        uris: &[String],
+        report: impl Fn(String) + 'static,
+        on_loaded: impl Fn() + 'static,
+    ) {
+        let count = uris.len() as u32;
+        let started = Instant::now();
+        let remaining = Rc::new(Cell::new(count));
+        let timed_out = Rc::new(Cell::new(0));
+        let report = Rc::new(report);
+        let on_loaded = Rc::new(on_loaded);
+
+        for uri in uris {
+            let webview = self.create_webview(None);
+
+            let done = Rc::new({
+                let (remaining, timed_out) = (remaining.clone(), timed_out.clone());
+                let (report, on_loaded) = (report.clone(), on_loaded.clone());
+                move |in_time: bool| {
+                    if !in_time {
+                        timed_out.set(timed_out.get() + 1);
+                    }
+                    remaining.set(remaining.get() - 1);
+                    if remaining.get() == 0 {
+                        report(summary("loaded", count, started.elapsed(), timed_out.get()));
+                        on_loaded();
+                    }
+                }
+            });
+
+            let handler = Rc::new(RefCell::new(None));
+            let timeout = Rc::new(RefCell::new(None));
+
+            let (done_c, handler_c, timeout_c) = (done.clone(), handler.clone(), timeout.clone());
+            let id = webview.connect_load_changed(move |webview, event| {
+                if event != LoadEvent::Finished {
+                    return;
+                }
+                if let Some(id) = handler_c.take() {
+                    webview.disconnect(id);
+                }
+                if let Some(source) = timeout_c.take() {
+                    source.remove();
+                }
+                done_c(true);
+            });
+            handler.replace(Some(id));
+
+            let webview_c = webview.clone();
+            let source = glib::timeout_add_local_once(PAGE_TIMEOUT, move || {
+                timeout.take();
+                if let Some(id) = handler.take() {
+                    webview_c.disconnect(id);
+                }
+                done(false);
+            });
+            timeout.replace(Some(source));
+
+            webview.load_uri(uri);
+            self.insert_tab(&webview, None, true);
+        }
+
+        report(summary("created", count, started.elapsed(), 0));
+    }
+}
+
+/// Writes `count` small pages to a temporary directory and returns their
+/// `file://` URIs, so the benchmark never touches the network.
+fn benchmark_pages(count: u32) -> std::io::Result<Vec<String>> {
+    let dir = std::env::temp_dir().join("tilde-benchmark");
+    (0..count)
+        .map(|i| {
+            let path = dir.join(format!("page-{}.html", i));
+            let html = format!(
+                "<!doctype html><title>Benchmark page {i}</title><p>Page {i}</p>",
+                i = i
+            );
+            paths::write_file(&path, &html)?;
+            Ok(gio::File::for_path(&path).uri().to_string())
+        })
+        .collect()
+}
+
+fn benchmark_count(arg: &str) -> Option<u32> {
+    match arg {
+        "" => Some(DEFAULT_TAB_COUNT),
+        _ => match arg.parse::<u32>() {
+            Ok(count) if count > 0 => Some(count),
+            _ => {
+                messages::error("Usage: benchmark-tabs [N]");
+                None
+            }
+        },
+    }
+}
+
+fn summary(what: &str, count: u32, elapsed: Duration, timed_out: u32) -> String {
+    let mut line = format!(
+        "benchmark: {} {} tabs in {:.1?} ({:.2?} per tab)",
+        what,
+        count,
+        elapsed,
+        elapsed / count
+    );
+    if timed_out > 0 {
+        line.push_str(&format!(
+            ", {} timed out after {:?}",
+            timed_out, PAGE_TIMEOUT
+        ));
+    }
+    line
+}
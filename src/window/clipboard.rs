@@ -0,0 +1,82 @@
+use gtk4::{gio, prelude::WidgetExt as _};
+use webkit6::prelude::WebViewExt as _;
+
+use super::{OpenTarget, Window};
+use crate::messages;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Yank {
+    Uri,
+    Title,
+    Markdown,
+    Org,
+}
+
+impl Window {
+    pub(super) fn yank(&self, what: Yank) {
+        let Some(webview) = self.current_webview() else {
+            return;
+        };
+        let uri = webview.uri().map(|u| u.to_string()).unwrap_or_default();
+        let title = webview
+            .title()
+            .map(|t| t.to_string())
+            .unwrap_or_else(|| uri.clone());
+
+        let (text, what) = match what {
+            Yank::Uri => (uri, "URL"),
+            Yank::Title => (title, "title"),
+            Yank::Markdown => (
+                format!("[{}]({})", title.replace(['[', ']'], ""), uri),
+                "Markdown link",
+            ),
+            Yank::Org => (
+                format!("[[{}][{}]]", uri, title.replace(['[', ']'], "")),
+                "Org link",
+            ),
+        };
+
+        // Fill both so the text can be pasted with Ctrl+V or middle click.
+        self.clipboard().set_text(&text);
+        self.primary_clipboard().set_text(&text);
+        messages::info(&format!("Yanked {}", what));
+    }
+
+    /// `p`/`P`: open the clipboard (or, failing that, the primary selection)
+    /// the same way the palette would treat typed text.
+    pub(super) fn paste_and_open(&self, in_new_tab: bool) {
+        self.clipboard().read_text_async(
+            None::<&gio::Cancellable>,
+            glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                move |result| {
+                    match result.ok().flatten().filter(|t| !t.trim().is_empty()) {
+                        Some(text) => window.open_pasted(&text, in_new_tab),
+                        None => window.primary_clipboard().read_text_async(
+                            None::<&gio::Cancellable>,
+                            glib::clone!(
+                                #[weak]
+                                window,
+                                move |result| {
+                                    if let Some(text) = result.ok().flatten() {
+                                        window.open_pasted(&text, in_new_tab);
+                                    }
+                                }
+                            ),
+                        ),
+                    }
+                }
+            ),
+        );
+    }
+
+    fn open_pasted(&self, text: &str, in_new_tab: bool) {
+        let target = if in_new_tab {
+            OpenTarget::NewTab
+        } else {
+            OpenTarget::CurrentTab
+        };
+        self.open_query(text.trim(), target);
+    }
+}
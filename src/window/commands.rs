@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+
+use glib::subclass::types::ObjectSubclassIsExt as _;
+use gtk4::{gio::prelude::ApplicationExt as _, prelude::GtkWindowExt as _};
+use webkit6::prelude::WebViewExt as _;
+
+use super::{OpenTarget, Window, tab_bar::TabBarMode};
+use crate::{messages, paths};
+
+const BINDINGS_FILE: &str = "bindings";
+
+pub struct CommandInfo {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub description: &'static str,
+}
+
+impl CommandInfo {
+    pub fn takes_args(&self) -> bool {
+        !self.usage.is_empty()
+    }
+}
+
+/// Palette commands, in the order they are offered as completions.
+pub const COMMANDS: &[CommandInfo] = &[
+    CommandInfo {
+        name: "open",
+        usage: "URL",
+        description: "Open a URL or search in the current tab",
+    },
+    CommandInfo {
+        name: "tabopen",
+        usage: "[URL]",
+        description: "Open a URL or search in a new tab, or the new-tab page",
+    },
+    CommandInfo {
+        name: "backopen",
+        usage: "URL",
+        description: "Open a URL or search in a background tab",
+    },
+    CommandInfo {
+        name: "reload",
+        usage: "",
+        description: "Reload the current page",
+    },
+    CommandInfo {
+        name: "close",
+        usage: "",
+        description: "Close the current tab, even if it is pinned",
+    },
+    CommandInfo {
+        name: "tabmove",
+        usage: "N|+N|-N",
+        description: "Move the current tab to position N or by N places",
+    },
+    CommandInfo {
+        name: "tab-pin",
+        usage: "",
+        description: "Pin or unpin the current tab",
+    },
+    CommandInfo {
+        name: "tab-clone",
+        usage: "",
+        description: "Duplicate the current tab with its history",
+    },
+    CommandInfo {
+        name: "tab-only",
+        usage: "",
+        description: "Close all other unpinned tabs",
+    },
+    CommandInfo {
+        name: "tab-close-left",
+        usage: "",
+        description: "Close unpinned tabs to the left",
+    },
+    CommandInfo {
+        name: "tab-close-right",
+        usage: "",
+        description: "Close unpinned tabs to the right",
+    },
+    CommandInfo {
+        name: "tab-give",
+        usage: "[WINDOW]",
+        description: "Move the current tab to another window, or a new one",
+    },
+    CommandInfo {
+        name: "tab-take",
+        usage: "WINDOW/TAB",
+        description: "Move a tab from another window into this one",
+    },
+    CommandInfo {
+        name: "tab-suspend",
+        usage: "",
+        description: "Suspend all background tabs now",
+    },
+    CommandInfo {
+        name: "tab-terminate",
+        usage: "",
+        description: "End the current tab's web process, as if it crashed",
+    },
+    CommandInfo {
+        name: "suspend-after",
+        usage: "MINUTES",
+        description: "Suspend background tabs idle this long (0 never)",
+    },
+    CommandInfo {
+        name: "workspace",
+        usage: "NAME",
+        description: "Switch to a workspace, creating it if needed",
+    },
+    CommandInfo {
+        name: "window-new",
+        usage: "[URL]",
+        description: "Open a new window",
+    },
+    CommandInfo {
+        name: "tabbar",
+        usage: "[hidden|strip|sidebar]",
+        description: "Choose how tabs are shown",
+    },
+    CommandInfo {
+        name: "benchmark-tabs",
+        usage: "[N]",
+        description: "Time opening N local pages in background tabs",
+    },
+    CommandInfo {
+        name: "userscripts",
+        usage: "[ID]",
+        description: "List userscripts, or turn one on or off",
+    },
+    CommandInfo {
+        name: "stylesheet-toggle",
+        usage: "[NAME]",
+        description: "Turn one user stylesheet, or all of them, on or off",
+    },
+    CommandInfo {
+        name: "adblock-toggle",
+        usage: "",
+        description: "Turn content blocking off or on for the current site",
+    },
+    CommandInfo {
+        name: "hide-element",
+        usage: "",
+        description: "Pick an element to hide on this site from now on",
+    },
+    CommandInfo {
+        name: "zoom",
+        usage: "[+|-|PERCENT]",
+        description: "Zoom the page in or out, or back to 100%",
+    },
+    CommandInfo {
+        name: "bookmark",
+        usage: "",
+        description: "Bookmark the current page, or remove its bookmark",
+    },
+    CommandInfo {
+        name: "set",
+        usage: "[-u PATTERN] NAME VALUE",
+        description: "Change a setting, or override it for sites matching PATTERN",
+    },
+    CommandInfo {
+        name: "get",
+        usage: "NAME",
+        description: "Show the value of a setting",
+    },
+    CommandInfo {
+        name: "reset",
+        usage: "NAME",
+        description: "Put a setting back to its default",
+    },
+    CommandInfo {
+        name: "messages",
+        usage: "[TEXT]",
+        description: "List this run's messages, or those containing TEXT",
+    },
+    CommandInfo {
+        name: "bind",
+        usage: "KEYS COMMAND",
+        description: "Run a command when KEYS are typed, in this and later runs",
+    },
+    CommandInfo {
+        name: "quit",
+        usage: "",
+        description: "Quit tilde",
+    },
+];
+
+/// Key sequences bound to commands out of the box. `:bind` adds to these.
+const DEFAULT_BINDINGS: &[(&str, &str)] = &[
+    (">>", "tabmove +"),
+    ("<<", "tabmove -"),
+    ("gp", "tab-pin"),
+    ("gc", "tab-clone"),
+    ("+", "zoom +"),
+    ("-", "zoom -"),
+    ("=", "zoom"),
+];
+
+impl Window {
+    pub(super) fn run_command(&self, cmd: &str) {
+        let args = command_args(cmd);
+
+        match command_name(cmd) {
+            "quit" | "q" => {
+                self.save_session(false);
+                if let Some(app) = self.application() {
+                    app.quit();
+                } else {
+                    self.close();
+                }
+            }
+            "reload" | "r" => {
+                if let Some(webview) = self.current_webview() {
+                    webview.reload();
+                }
+            }
+            "close" | "d" => self.close_current_tab(),
+            "open" | "o" => self.open_query(args, OpenTarget::CurrentTab),
+            "tabopen" | "t" if args.is_empty() => self.open_new_tab_page(),
+            "tabopen" | "t" => self.open_query(args, OpenTarget::NewTab),
+            "backopen" | "bg" => self.open_query(args, OpenTarget::BackgroundTab),
+            "tabbar" => match TabBarMode::from_name(args) {
+                Some(mode) => self.set_tab_bar_mode(mode),
+                None => self.cycle_tab_bar_mode(),
+            },
+            "tabmove" => self.move_current_tab(args),
+            "tab-pin" => self.toggle_pin(),
+            "tab-clone" => self.clone_current_tab(),
+            "tab-only" => self.close_other_tabs(|_| true),
+            "tab-close-left" => self.close_other_tabs(|left| left),
+            "tab-close-right" => self.close_other_tabs(|left| !left),
+            "tab-give" => self.give_current_tab(args),
+            "tab-take" => self.take_tab(args),
+            "tab-suspend" => self.suspend_background_tabs(),
+            "tab-terminate" => self.terminate_current_tab(),
+            "suspend-after" => self.set_suspend_after(args),
+            "workspace" | "ws" => self.switch_workspace(args),
+            "window-new" => self.open_window(args),
+            "userscripts" => self.toggle_userscript(args),
+            "stylesheet-toggle" => self.toggle_stylesheet(args),
+            "adblock-toggle" => self.toggle_adblock(),
+            "hide-element" => self.hide_element(),
+            "zoom" => self.zoom(args),
+            "bookmark" => self.toggle_bookmark(),
+            "set" => self.set_command(args),
+            "get" => self.get_setting(args),
+            "reset" => self.reset_setting(args),
+            "bind" => self.bind(args),
+            "benchmark-tabs" => self.benchmark_tabs(args),
+            "messages" => self.show_messages(args),
+            _ => messages::error(&format!("Unknown command: {}", cmd)),
+        }
+    }
+
+    /// Known values for the argument of `name`, with a short description.
+    pub(super) fn complete_args(&self, name: &str) -> Vec<(String, String)> {
+        match name {
+            "workspace" | "ws" => self
+                .workspaces()
+                .into_iter()
+                .map(|(name, tabs)| (name, format!("{} tabs", tabs)))
+                .collect(),
+            "userscripts" => self.userscript_args(),
+            "stylesheet-toggle" => self.stylesheet_args(),
+            "set" => self.set_args(),
+            "get" | "reset" => self.setting_names(),
+            "tabbar" => ["hidden", "strip", "sidebar"]
+                .iter()
+                .map(|mode| (mode.to_string(), String::new()))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The command bound to exactly `seq`, if any. User bindings win.
+    pub(super) fn binding_for(&self, seq: &str) -> Option<String> {
+        if let Some(command) = self.imp().bindings.borrow().get(seq) {
+            return Some(command.clone());
+        }
+
+        DEFAULT_BINDINGS
+            .iter()
+            .find(|(keys, _)| *keys == seq)
+            .map(|(_, command)| command.to_string())
+    }
+
+    /// Whether `seq` is the start of (or equal to) some binding.
+    pub(super) fn is_binding_prefix(&self, seq: &str) -> bool {
+        self.imp()
+            .bindings
+            .borrow()
+            .keys()
+            .any(|keys| keys.starts_with(seq))
+            || DEFAULT_BINDINGS
+                .iter()
+                .any(|(keys, _)| keys.starts_with(seq))
+    }
+
+    pub(super) fn load_bindings(&self) {
+        self.imp().bindings.replace(saved_bindings());
+    }
+
+    /// `:bind KEYS COMMAND` binds in every window and keeps the binding for
+    /// later runs.
+    fn bind(&self, args: &str) {
+        let Some((keys, command)) = args.split_once(char::is_whitespace) else {
+            messages::error("Usage: bind KEYS COMMAND");
+            return;
+        };
+        let (keys, command) = (keys.to_string(), command.trim().to_string());
+
+        let mut bindings = saved_bindings();
+        bindings.insert(keys.clone(), command.clone());
+        let mut lines: Vec<String> = bindings
+            .iter()
+            .map(|(keys, command)| format!("{}\t{}", keys, command))
+            .collect();
+        lines.sort();
+        if let Err(err) = paths::write_list(BINDINGS_FILE, &lines) {
+            messages::error(&format!("Failed to save bindings: {}", err));
+        }
+
+        for window in self.app_windows() {
+            window
+                .imp()
+                .bindings
+                .borrow_mut()
+                .insert(keys.clone(), command.clone());
+        }
+    }
+}
+
+/// Bindings added with `:bind`. One per line: <keys>\t<command>
+fn saved_bindings() -> HashMap<String, String> {
+    paths::read_list(BINDINGS_FILE)
+        .iter()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(keys, command)| (keys.to_string(), command.to_string()))
+        .collect()
+}
+
+/// The first word of a palette command, e.g. `open` for `open example.com`.
+pub fn command_name(cmd: &str) -> &str {
+    cmd.split_whitespace().next().unwrap_or("")
+}
+
+pub fn command_args(cmd: &str) -> &str {
+    cmd.trim_start()
+        .split_once(char::is_whitespace)
+        .map(|(_, args)| args.trim())
+        .unwrap_or("")
+}
+
+/// Splits `args` at whitespace, keeping quoted ('...' or "...") parts
+/// together, e.g. for patterns with spaces or shell-style quoting.
+pub fn split_args(args: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+
+    for ch in args.chars() {
+        match quote {
+            Some(q) if ch == q => quote = None,
+            Some(_) => word.get_or_insert_default().push(ch),
+            None if ch == '\'' || ch == '"' => {
+                quote = Some(ch);
+                word.get_or_insert_default();
+            }
+            None if ch.is_whitespace() => words.extend(word.take()),
+            None => word.get_or_insert_default().push(ch),
+        }
+    }
+    words.extend(word);
+    words
+}
@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use glib::{
+    object::{Cast as _, ObjectExt as _},
+    subclass::types::ObjectSubclassIsExt as _,
+};
+use webkit6::{WebProcessTerminationReason, WebView, prelude::WebViewExt as _};
+
+use super::{
+    Window,
+    suspend::{is_suspended, tab_uri},
+    window_of,
+};
+use crate::{messages, pages};
+
+/// Web processes lost in this run, for the crash messages.
+static CRASHES: AtomicU32 = AtomicU32::new(0);
+
+fn describe(reason: WebProcessTerminationReason) -> &'static str {
+    match reason {
+        WebProcessTerminationReason::Crashed => "crashed",
+        WebProcessTerminationReason::ExceededMemoryLimit => "ran out of memory",
+        WebProcessTerminationReason::TerminatedByApi => "was terminated",
+        _ => "stopped",
+    }
+}
+
+impl Window {
+    /// Reloads background tabs that crashed once they are focused.
+    pub(super) fn setup_crash_recovery(&self) {
+        self.imp().notebook.connect_switch_page(|_, page, _| {
+            if let Some(webview) = page.downcast_ref::<WebView>()
+                && let Some(uri) = unsafe { webview.steal_data::<String>("crashed") }
+            {
+                webview.load_uri(&uri);
+            }
+        });
+    }
+
+    /// Handles the web process behind `webview` going away. The current tab
+    /// shows what happened with a way to reload; background tabs reload when
+    /// focused. Suspending a tab ends its process on purpose, which is left
+    /// alone.
+    pub(super) fn track_crashes(&self, webview: &WebView) {
+        webview.connect_web_process_terminated(|webview, reason| {
+            if is_suspended(webview) {
+                return;
+            }
+            let uri = tab_uri(webview).unwrap_or_default();
+            let what_happened = describe(reason);
+
+            let crashes = CRASHES.fetch_add(1, Ordering::Relaxed) + 1;
+            messages::error(&format!(
+                "Page {} {} (crash {} this run)",
+                uri, what_happened, crashes
+            ));
+            if uri.is_empty() {
+                return;
+            }
+
+            let Some(window) = window_of(webview) else {
+                return;
+            };
+            if window.current_webview().as_ref() == Some(webview) {
+                webview.load_alternate_html(&pages::crash_page(&uri, what_happened), &uri, None);
+            } else {
+                unsafe { webview.set_data("crashed", uri) }
+            }
+        });
+    }
+
+    /// `:tab-terminate` ends the current tab's web process, as a crash would.
+    pub(super) fn terminate_current_tab(&self) {
+        if let Some(webview) = self.current_webview() {
+            webview.terminate_web_process();
+        }
+    }
+}
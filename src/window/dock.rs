@@ -0,0 +1,431 @@
+use glib::{
+    object::{Cast as _, ObjectExt as _},
+    subclass::types::ObjectSubclassIsExt as _,
+};
+use gtk4::{
+    Label,
+    prelude::{BoxExt as _, GtkApplicationExt as _, GtkWindowExt as _, WidgetExt as _},
+};
+use webkit6::{LoadEvent, WebView, prelude::WebViewExt as _};
+
+use super::{Window, site_settings::is_passthrough, window_of};
+use crate::{
+    messages::{self, Level, Message},
+    settings,
+};
+
+fn is_editable(webview: &WebView) -> bool {
+    unsafe {
+        webview
+            .data::<bool>("is_editable")
+            .is_some_and(|editable| *editable.as_ptr())
+    }
+}
+
+fn scroll_percent(webview: &WebView) -> Option<u32> {
+    unsafe {
+        webview
+            .data::<Option<u32>>("scroll_percent")
+            .and_then(|percent| *percent.as_ptr())
+    }
+}
+
+fn has_insecure_content(webview: &WebView) -> bool {
+    unsafe {
+        webview
+            .data::<bool>("insecure_content")
+            .is_some_and(|insecure| *insecure.as_ptr())
+    }
+}
+
+/// Records whether an editable element has focus in `webview`.
+pub fn show_edit_state(webview: &WebView, editable: bool) {
+    unsafe { webview.set_data("is_editable", editable) }
+
+    if let Some(window) = window_of(webview) {
+        window.update_mode_label();
+    }
+}
+
+/// Records how far down the page in `webview` is scrolled, or `None` for
+/// pages that don't scroll.
+pub fn show_scroll_percent(webview: &WebView, percent: Option<u32>) {
+    unsafe { webview.set_data("scroll_percent", percent) }
+
+    if let Some(window) = window_of(webview) {
+        window.update_scroll_label();
+    }
+}
+
+/// How the dock shows itself, from the `dock.mode` setting.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DockMode {
+    /// Only shown and hidden with `D`
+    Manual,
+    Always,
+    /// `D` shows it for `dock.hide-after` seconds
+    AutoHide,
+    /// Also shown briefly on tab switches and navigation
+    Flash,
+}
+
+fn dock_mode() -> DockMode {
+    match settings::text("dock.mode").as_str() {
+        "always" => DockMode::Always,
+        "auto-hide" => DockMode::AutoHide,
+        "flash" => DockMode::Flash,
+        _ => DockMode::Manual,
+    }
+}
+
+fn hide_after() -> u32 {
+    settings::int("dock.hide-after") as u32
+}
+
+/// The text for the security segment, and whether it is a warning.
+fn security_state(webview: &WebView) -> Option<(&'static str, bool)> {
+    let uri = webview.uri()?;
+
+    if uri.starts_with("http://") {
+        Some(("not secure", true))
+    } else if !uri.starts_with("https://") {
+        // tilde's own pages, blank pages, local files
+        None
+    } else if has_insecure_content(webview) {
+        Some(("mixed content", true))
+    } else {
+        match webview.tls_info() {
+            Some((_, errors)) if errors.is_empty() => Some(("secure", false)),
+            Some(_) => Some(("certificate error", true)),
+            // Not known until the page is committed
+            None => None,
+        }
+    }
+}
+
+impl Window {
+    fn segment(&self, name: &str) -> Option<Label> {
+        let imp = self.imp();
+        let label = match name {
+            "progress" => &imp.progress_label,
+            "security" => &imp.security_label,
+            "mode" => &imp.mode_label,
+            "keys" => &imp.keys_label,
+            "scroll" => &imp.scroll_label,
+            "zoom" => &imp.zoom_label,
+            "blocked" => &imp.blocked_label,
+            "tabs" => &imp.tab_label,
+            _ => return None,
+        };
+        Some(label.get())
+    }
+
+    /// Shows `text` in the segment `name`, if `dock.segments` has it. Empty
+    /// segments are hidden.
+    pub(super) fn show_segment(&self, name: &str, text: &str) {
+        let Some(label) = self.segment(name) else {
+            return;
+        };
+        let enabled = settings::list("dock.segments").iter().any(|s| s == name);
+
+        label.set_visible(enabled && !text.is_empty());
+        label.set_label(text);
+    }
+
+    /// Orders the segments as `dock.segments` lists them and applies the
+    /// `dock.mode`, now and whenever the settings change.
+    pub(super) fn setup_dock(&self) {
+        self.arrange_dock_segments();
+        self.apply_dock_mode();
+
+        let window = self.downgrade();
+        let id = messages::connect(move |message| {
+            if let Some(window) = window.upgrade()
+                && window.is_active_window()
+            {
+                window.show_message(message);
+            }
+        });
+        self.imp().message_listener.replace(Some(id));
+
+        let window = self.downgrade();
+        let id = settings::connect(move |name| {
+            let Some(window) = window.upgrade() else {
+                return;
+            };
+            match name {
+                "dock.segments" => {
+                    window.arrange_dock_segments();
+                    window.update_dock_info();
+                }
+                "dock.mode" => window.apply_dock_mode(),
+                _ => {}
+            }
+        });
+        self.imp().settings_listeners.borrow_mut().push(id);
+
+        self.imp().notebook.connect_switch_page(glib::clone!(
+            #[weak(rename_to = window)]
+            self,
+            move |_, _, _| {
+                if dock_mode() == DockMode::Flash {
+                    window.reveal_dock_briefly();
+                }
+            }
+        ));
+
+        self.imp()
+            .command_palette_container
+            .connect_visible_notify(glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                move |_| window.update_mode_label()
+            ));
+    }
+
+    fn arrange_dock_segments(&self) {
+        let segments = &self.imp().dock_segments;
+        let mut previous: Option<Label> = None;
+
+        for name in settings::list("dock.segments") {
+            if let Some(label) = self.segment(&name) {
+                segments.reorder_child_after(&label, previous.as_ref());
+                previous = Some(label);
+            }
+        }
+    }
+
+    /// Keeps the dock's view of `webview` current while it loads.
+    pub(super) fn track_dock_state(&self, webview: &WebView) {
+        for property in ["estimated-load-progress", "is-loading"] {
+            webview.connect_notify_local(Some(property), |webview, _| {
+                if let Some(window) = window_of(webview) {
+                    window.update_progress_label();
+                    window.reveal_dock_for_load(webview);
+                }
+            });
+        }
+
+        webview.connect_load_changed(|webview, event| {
+            if event == LoadEvent::Committed {
+                unsafe { webview.set_data("insecure_content", false) }
+                show_scroll_percent(webview, None);
+            }
+            let Some(window) = window_of(webview) else {
+                return;
+            };
+            window.update_security_label();
+
+            if event == LoadEvent::Committed
+                && dock_mode() == DockMode::Flash
+                && window.current_webview().as_ref() == Some(webview)
+            {
+                window.reveal_dock_briefly();
+            }
+        });
+
+        webview.connect_insecure_content_detected(|webview, _| {
+            unsafe { webview.set_data("insecure_content", true) }
+            if let Some(window) = window_of(webview) {
+                window.update_security_label();
+            }
+        });
+    }
+
+    /// With `dock.reveal-on-load`, shows the dock while the current tab
+    /// loads and hides it again afterwards, unless it was already shown.
+    fn reveal_dock_for_load(&self, webview: &WebView) {
+        let imp = self.imp();
+        if self.current_webview().as_ref() != Some(webview) {
+            return;
+        }
+
+        if webview.is_loading() {
+            if settings::flag("dock.reveal-on-load") && !imp.dock_revealer.reveals_child() {
+                imp.dock_shown_for_load.set(true);
+                imp.dock_revealer.set_reveal_child(true);
+            }
+        } else if imp.dock_shown_for_load.replace(false) {
+            imp.dock_revealer.set_reveal_child(false);
+        }
+    }
+
+    fn apply_dock_mode(&self) {
+        if dock_mode() == DockMode::Always {
+            self.cancel_dock_hide();
+            self.update_dock_info();
+            self.imp().dock_revealer.set_reveal_child(true);
+        }
+    }
+
+    /// `D` shows or hides the dock. In auto-hide mode it hides again by
+    /// itself.
+    pub(super) fn toggle_dock(&self) {
+        let imp = self.imp();
+        let shown_for_now = imp.dock_hide_timer.borrow().is_some() || imp.dock_shown_for_load.get();
+        self.cancel_dock_hide();
+        imp.dock_shown_for_load.set(false);
+
+        if imp.dock_revealer.reveals_child() && !shown_for_now {
+            imp.dock_revealer.set_reveal_child(false);
+        } else if dock_mode() == DockMode::AutoHide {
+            self.reveal_dock_briefly();
+        } else {
+            self.update_dock_info();
+            imp.dock_revealer.set_reveal_child(true);
+        }
+    }
+
+    /// Shows the dock for `dock.hide-after` seconds. A dock that is shown
+    /// for good stays that way.
+    fn reveal_dock_briefly(&self) {
+        let imp = self.imp();
+        let revealed = imp.dock_revealer.reveals_child();
+        let temporary = imp.dock_hide_timer.borrow().is_some() || imp.dock_shown_for_load.get();
+        if revealed && !temporary {
+            return;
+        }
+
+        self.update_dock_info();
+        imp.dock_revealer.set_reveal_child(true);
+
+        self.cancel_dock_hide();
+        let id = glib::timeout_add_seconds_local_once(
+            hide_after(),
+            glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                move || {
+                    let imp = window.imp();
+                    imp.dock_hide_timer.take();
+                    if dock_mode() != DockMode::Always && !imp.dock_shown_for_load.get() {
+                        imp.dock_revealer.set_reveal_child(false);
+                    }
+                }
+            ),
+        );
+        imp.dock_hide_timer.replace(Some(id));
+    }
+
+    fn cancel_dock_hide(&self) {
+        if let Some(id) = self.imp().dock_hide_timer.take() {
+            id.remove();
+        }
+    }
+
+    /// Whether messages should show in this window: the one focused last.
+    fn is_active_window(&self) -> bool {
+        self.application()
+            .and_then(|app| app.active_window())
+            .is_none_or(|window| window == *self.upcast_ref::<gtk4::Window>())
+    }
+
+    /// Shows `message` in place of the URI for a few seconds, bringing up
+    /// the dock if it is hidden.
+    fn show_message(&self, message: &Message) {
+        let imp = self.imp();
+        imp.message.replace(Some(message.clone()));
+
+        if let Some(id) = imp.message_timer.take() {
+            id.remove();
+        }
+        let id = glib::timeout_add_seconds_local_once(
+            hide_after(),
+            glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                move || {
+                    let imp = window.imp();
+                    imp.message_timer.take();
+                    imp.message.take();
+                    window.update_dock_info();
+                }
+            ),
+        );
+        imp.message_timer.replace(Some(id));
+
+        self.update_dock_info();
+        self.reveal_dock_briefly();
+    }
+
+    /// Puts the current message, if any, over the URI.
+    pub(super) fn update_message_label(&self) {
+        let imp = self.imp();
+        let label = &imp.uri_label;
+        for class in ["message", "message-warning", "message-error"] {
+            label.remove_css_class(class);
+        }
+
+        if let Some(message) = imp.message.borrow().as_ref() {
+            label.set_label(&message.text);
+            label.add_css_class(match message.level {
+                Level::Warning => "message-warning",
+                Level::Error => "message-error",
+                Level::Debug | Level::Info => "message",
+            });
+        }
+    }
+
+    /// Refreshes the segments that depend on the current tab.
+    pub(super) fn update_dock_segments(&self) {
+        self.update_progress_label();
+        self.update_security_label();
+        self.update_mode_label();
+        self.update_keys_label();
+        self.update_scroll_label();
+        self.update_zoom_label();
+        self.update_blocked_label();
+    }
+
+    fn update_progress_label(&self) {
+        let text = match self.current_webview() {
+            Some(webview) if webview.is_loading() => {
+                format!("{:.0}%", webview.estimated_load_progress() * 100.0)
+            }
+            _ => String::new(),
+        };
+        self.show_segment("progress", &text);
+    }
+
+    fn update_security_label(&self) {
+        let state = self.current_webview().and_then(|w| security_state(&w));
+        let (text, warning) = state.unwrap_or(("", false));
+
+        self.show_segment("security", text);
+        let label = &self.imp().security_label;
+        if warning {
+            label.add_css_class("warning");
+        } else {
+            label.remove_css_class("warning");
+        }
+    }
+
+    pub(super) fn update_mode_label(&self) {
+        let webview = self.current_webview();
+        let mode = if self.imp().command_palette_container.is_visible() {
+            "COMMAND"
+        } else if webview.as_ref().is_some_and(is_editable) {
+            "INSERT"
+        } else if webview.as_ref().is_some_and(is_passthrough) {
+            "PASSTHROUGH"
+        } else {
+            "NORMAL"
+        };
+        self.show_segment("mode", mode);
+    }
+
+    pub(super) fn update_keys_label(&self) {
+        let keys = self.imp().pending_keys.borrow().clone();
+        self.show_segment("keys", &keys);
+    }
+
+    fn update_scroll_label(&self) {
+        let text = match self.current_webview().and_then(|w| scroll_percent(&w)) {
+            Some(0) => "top".to_string(),
+            Some(100) => "bottom".to_string(),
+            Some(percent) => format!("{}%", percent),
+            None => String::new(),
+        };
+        self.show_segment("scroll", &text);
+    }
+}
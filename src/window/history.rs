@@ -0,0 +1,54 @@
+use webkit6::{LoadEvent, WebView, prelude::WebViewExt as _};
+
+use super::{
+    Window,
+    suspend::{is_suspended, tab_title, tab_uri},
+};
+use crate::{bookmarks, history, messages, settings};
+
+impl Window {
+    /// Counts each page `webview` finishes loading as a visit.
+    pub(super) fn track_visits(&self, webview: &WebView) {
+        webview.connect_load_changed(|webview, event| {
+            if event == LoadEvent::Finished
+                && !is_suspended(webview)
+                && let Some(uri) = webview.uri()
+            {
+                history::record_visit(&uri, &tab_title(webview).unwrap_or_default());
+            }
+        });
+    }
+
+    /// Keeps `webview`, which is being closed, for the start page.
+    pub(super) fn remember_closed(&self, webview: &WebView) {
+        if let Some(uri) = tab_uri(webview) {
+            history::record_closed(&uri, &tab_title(webview).unwrap_or_default());
+        }
+    }
+
+    /// Opens the `start.new-tab-page` in a new tab.
+    pub(super) fn open_new_tab_page(&self) {
+        self.new_tab(&settings::text("start.new-tab-page"));
+    }
+
+    /// `:bookmark` bookmarks the current page, or removes its bookmark.
+    pub(super) fn toggle_bookmark(&self) {
+        let Some(webview) = self.current_webview() else {
+            return;
+        };
+        let Some(uri) = tab_uri(&webview) else {
+            return;
+        };
+
+        let bookmarked = bookmarks::toggle(&uri, &tab_title(&webview).unwrap_or_default());
+        messages::info(&format!(
+            "{} {}",
+            if bookmarked {
+                "Bookmarked"
+            } else {
+                "Removed bookmark for"
+            },
+            uri
+        ));
+    }
+}
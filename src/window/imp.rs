@@ -1,17 +1,35 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+
 use glib::subclass::InitializingObject;
 use gtk4::subclass::prelude::*;
 use gtk4::{
-    Box as GtkBox, CompositeTemplate, Label, ListBox, Notebook, Revealer, SearchEntry, glib,
+    Box as GtkBox, CompositeTemplate, Label, ListBox, Notebook, Revealer, ScrolledWindow,
+    SearchEntry, glib,
 };
 
+use super::OpenTarget;
+use super::marks::Mark;
+use super::tab_bar::TabBarMode;
+use crate::messages::{self, Message};
+use crate::profile::DEFAULT_PROFILE;
+use crate::session::{DEFAULT_WORKSPACE, WorkspaceState};
+use crate::settings;
+
 #[derive(CompositeTemplate, Default)]
 #[template(resource = "/templates/window.ui")]
 pub struct Window {
     #[template_child]
     pub notebook: TemplateChild<Notebook>,
     #[template_child]
+    pub tab_sidebar: TemplateChild<ScrolledWindow>,
+    #[template_child]
+    pub tab_list: TemplateChild<ListBox>,
+    #[template_child]
     pub command_palette_container: TemplateChild<GtkBox>,
     #[template_child]
+    pub palette_header: TemplateChild<Label>,
+    #[template_child]
     pub command_entry: TemplateChild<SearchEntry>,
     #[template_child]
     pub results_list: TemplateChild<ListBox>,
@@ -21,9 +39,54 @@ pub struct Window {
     #[template_child]
     pub profile_label: TemplateChild<Label>,
     #[template_child]
+    pub workspace_label: TemplateChild<Label>,
+    #[template_child]
     pub uri_label: TemplateChild<Label>,
     #[template_child]
+    pub dock_segments: TemplateChild<GtkBox>,
+    #[template_child]
+    pub progress_label: TemplateChild<Label>,
+    #[template_child]
+    pub security_label: TemplateChild<Label>,
+    #[template_child]
+    pub mode_label: TemplateChild<Label>,
+    #[template_child]
+    pub keys_label: TemplateChild<Label>,
+    #[template_child]
+    pub scroll_label: TemplateChild<Label>,
+    #[template_child]
+    pub zoom_label: TemplateChild<Label>,
+    #[template_child]
+    pub blocked_label: TemplateChild<Label>,
+    #[template_child]
     pub tab_label: TemplateChild<Label>,
+
+    // Keys typed so far in a multi-key binding such as `ma`
+    pub pending_keys: RefCell<String>,
+    // Whether the dock is only showing because a page is loading
+    pub dock_shown_for_load: Cell<bool>,
+    // Hides a dock that is only shown for a moment
+    pub dock_hide_timer: RefCell<Option<glib::SourceId>>,
+    // Transient message shown in the dock, and what clears it
+    pub message: RefCell<Option<Message>>,
+    pub message_timer: RefCell<Option<glib::SourceId>>,
+    // Listeners on the app-wide messages and settings, removed on dispose
+    pub message_listener: RefCell<Option<messages::ListenerId>>,
+    pub settings_listeners: RefCell<Vec<settings::ListenerId>>,
+    pub jump_list: RefCell<Vec<Mark>>,
+    pub palette_target: Cell<OpenTarget>,
+    // Tab id -> id of the tab it was opened from
+    pub openers: RefCell<HashMap<u64, u64>>,
+    pub tab_bar_mode: Cell<TabBarMode>,
+    // Ids of pinned tabs
+    pub pinned: RefCell<HashSet<u64>>,
+    // Key sequence -> palette command, added with `:bind`
+    pub bindings: RefCell<HashMap<String, String>>,
+    pub number: Cell<u32>,
+    pub profile: RefCell<String>,
+    // Name of the workspace whose tabs are in the notebook
+    pub workspace: RefCell<String>,
+    pub inactive_workspaces: RefCell<Vec<WorkspaceState>>,
 }
 
 #[glib::object_subclass]
@@ -44,14 +107,36 @@ impl ObjectSubclass for Window {
 impl ObjectImpl for Window {
     fn constructed(&self) {
         self.parent_constructed();
+        self.profile.replace(DEFAULT_PROFILE.to_string());
+        self.workspace.replace(DEFAULT_WORKSPACE.to_string());
 
         self.obj().load_css();
         self.obj().setup_shortcuts();
         self.obj().setup_palette_controller();
-        self.obj().new_tab("https://duckduckgo.com");
+        self.obj().load_bindings();
+        self.obj().setup_tab_bar();
+        self.obj().setup_suspension();
+        self.obj().setup_crash_recovery();
+        self.obj().watch_settings();
+        self.obj().watch_zoom_setting();
+        self.obj().setup_dock();
+    }
+
+    fn dispose(&self) {
+        if let Some(id) = self.message_listener.take() {
+            messages::disconnect(id);
+        }
+        for id in self.settings_listeners.take() {
+            settings::disconnect(id);
+        }
     }
 }
 
 impl WidgetImpl for Window {}
-impl WindowImpl for Window {}
+impl WindowImpl for Window {
+    fn close_request(&self) -> glib::Propagation {
+        self.obj().save_session(true);
+        self.parent_close_request()
+    }
+}
 impl ApplicationWindowImpl for Window {}
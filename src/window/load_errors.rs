@@ -0,0 +1,176 @@
+use glib::object::ObjectExt as _;
+use gtk4::{gdk, gio};
+use webkit6::{LoadEvent, NetworkError, PolicyError, WebView, prelude::WebViewExt as _};
+
+use super::{Window, search_url};
+use crate::{messages, pages};
+
+/// What went wrong loading a tab's page, kept while its error page shows.
+#[derive(Clone)]
+struct LoadError {
+    uri: String,
+    /// The certificate an exception would be for, and its host
+    certificate: Option<(gio::TlsCertificate, String)>,
+}
+
+fn load_error(webview: &WebView) -> Option<LoadError> {
+    unsafe {
+        webview
+            .data::<LoadError>("load_error")
+            .map(|error| error.as_ref().clone())
+    }
+}
+
+/// What the user most likely typed to get to `uri`, as the palette adds
+/// `https://` to anything that looks like an address.
+fn typed_text(uri: &str) -> &str {
+    let text = uri
+        .strip_prefix("https://")
+        .or_else(|| uri.strip_prefix("http://"))
+        .unwrap_or(uri);
+    text.strip_suffix('/').unwrap_or(text)
+}
+
+/// A heading and explanation for `err`, or nothing for failures that need
+/// no page, like loads that were cancelled or turned into downloads.
+fn describe(err: &glib::Error) -> Option<(&'static str, Vec<String>)> {
+    if err.matches(NetworkError::Cancelled)
+        || err.matches(PolicyError::FrameLoadInterruptedByPolicyChange)
+    {
+        return None;
+    }
+
+    let (heading, hint) = if err.matches(gio::ResolverError::NotFound)
+        || err.matches(gio::ResolverError::TemporaryFailure)
+    {
+        (
+            "Server not found",
+            Some("Check the address for typos, or search for it instead."),
+        )
+    } else if err.matches(gio::IOErrorEnum::ConnectionRefused) {
+        (
+            "Connection refused",
+            Some("The server is up but not accepting connections here."),
+        )
+    } else if err.matches(gio::IOErrorEnum::TimedOut) {
+        ("Connection timed out", None)
+    } else if err.matches(gio::IOErrorEnum::NetworkUnreachable)
+        || err.matches(gio::IOErrorEnum::HostUnreachable)
+    {
+        ("Network unreachable", Some("Check your connection."))
+    } else {
+        ("Page failed to load", None)
+    };
+
+    let mut details = vec![err.message().to_string()];
+    details.extend(hint.map(str::to_string));
+    Some((heading, details))
+}
+
+/// Why a certificate was rejected, one sentence per problem.
+fn certificate_problems(errors: gio::TlsCertificateFlags) -> Vec<String> {
+    use gio::TlsCertificateFlags as Flags;
+
+    [
+        (
+            Flags::UNKNOWN_CA,
+            "It isn't signed by an authority this system trusts.",
+        ),
+        (Flags::BAD_IDENTITY, "It was issued for a different site."),
+        (Flags::NOT_ACTIVATED, "It isn't valid yet."),
+        (Flags::EXPIRED, "It has expired."),
+        (Flags::REVOKED, "It has been revoked."),
+        (Flags::INSECURE, "It relies on insecure cryptography."),
+        (Flags::GENERIC_ERROR, "It could not be checked."),
+    ]
+    .iter()
+    .filter(|(flag, _)| errors.contains(*flag))
+    .map(|(_, problem)| problem.to_string())
+    .collect()
+}
+
+fn show_error_page(webview: &WebView, error: LoadError, heading: &str, details: &[String]) {
+    let search = format!("Search for {} instead", typed_text(&error.uri));
+    let mut keys = vec![("r", "Try again"), ("s", search.as_str())];
+    if error.certificate.is_some() {
+        keys.push(("a", "Trust this certificate for the site and try again"));
+    }
+    let html = pages::error_page(&error.uri, heading, details, &keys);
+
+    // The error page's own load mustn't clear what it is about
+    let uri = error.uri.clone();
+    unsafe {
+        webview.set_data("load_error", error);
+        webview.set_data("error_page_loading", true);
+    }
+    webview.load_alternate_html(&html, &uri, None);
+}
+
+impl Window {
+    /// Replaces WebKit's error pages with ours, which offer keys to retry,
+    /// search or trust a certificate.
+    pub(super) fn track_load_errors(&self, webview: &WebView) {
+        webview.connect_load_failed(|webview, _, uri, err| {
+            let Some((heading, details)) = describe(err) else {
+                return false;
+            };
+            messages::debug(&format!("Failed to load {}: {}", uri, err));
+
+            let error = LoadError {
+                uri: uri.to_string(),
+                certificate: None,
+            };
+            show_error_page(webview, error, heading, &details);
+            true
+        });
+
+        webview.connect_load_failed_with_tls_errors(|webview, uri, certificate, errors| {
+            messages::debug(&format!("Certificate of {} rejected: {:?}", uri, errors));
+
+            let host = glib::Uri::parse(uri, glib::UriFlags::NONE)
+                .ok()
+                .and_then(|uri| uri.host())
+                .map(|host| host.to_string());
+            let error = LoadError {
+                uri: uri.to_string(),
+                certificate: host.map(|host| (certificate.clone(), host)),
+            };
+
+            let mut details = vec!["The site's certificate can't be trusted.".to_string()];
+            details.extend(certificate_problems(errors));
+            show_error_page(webview, error, "Certificate problem", &details);
+            true
+        });
+
+        webview.connect_load_changed(|webview, event| {
+            if event == LoadEvent::Started
+                && unsafe { webview.steal_data::<bool>("error_page_loading") }.is_none()
+            {
+                unsafe { webview.steal_data::<LoadError>("load_error") };
+            }
+        });
+    }
+
+    /// Runs what `key` does on the error page in `webview`, if it shows
+    /// one. Returns whether the key was used.
+    pub(super) fn handle_error_page_key(&self, webview: &WebView, key: gdk::Key) -> bool {
+        let Some(error) = load_error(webview) else {
+            return false;
+        };
+
+        match key {
+            gdk::Key::r => webview.load_uri(&error.uri),
+            gdk::Key::s => webview.load_uri(&search_url(typed_text(&error.uri))),
+            gdk::Key::a => {
+                let Some((certificate, host)) = &error.certificate else {
+                    return false;
+                };
+                self.profile().allow_certificate(host, certificate);
+                messages::info(&format!("Trusting the certificate of {}", host));
+                webview.load_uri(&error.uri);
+            }
+            _ => return false,
+        }
+        true
+    }
+}
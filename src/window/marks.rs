@@ -0,0 +1,236 @@
+use std::{cell::RefCell, collections::HashMap, fs, rc::Rc};
+
+use glib::{object::ObjectExt as _, subclass::types::ObjectSubclassIsExt as _};
+use gtk4::gio;
+use webkit6::{LoadEvent, WebView, prelude::WebViewExt as _};
+
+use super::{Window, suspend::tab_uri};
+use crate::{
+    messages::{self, Level},
+    paths, scripts,
+};
+
+const MARKS_FILE: &str = "marks";
+const JUMP_LIST_LIMIT: usize = 100;
+
+#[derive(Clone, Debug)]
+pub struct Mark {
+    pub uri: String,
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Lowercase marks belong to a single page, uppercase marks are global and
+/// remember which URL they were set on.
+#[derive(Default)]
+struct MarkStore {
+    local: HashMap<(String, char), Mark>,
+    global: HashMap<char, Mark>,
+}
+
+thread_local! {
+    // Shared by every window, so that none saves over another's marks
+    static MARKS: RefCell<Option<MarkStore>> = const { RefCell::new(None) };
+}
+
+fn with_marks<T>(f: impl FnOnce(&mut MarkStore) -> T) -> T {
+    MARKS.with_borrow_mut(|marks| f(marks.get_or_insert_with(MarkStore::load)))
+}
+
+impl MarkStore {
+    fn load() -> Self {
+        let mut store = Self::default();
+        let Ok(contents) = fs::read_to_string(paths::data_file(MARKS_FILE)) else {
+            return store;
+        };
+
+        // One mark per line: <letter>\t<x>\t<y>\t<uri>
+        for line in contents.lines() {
+            let mut fields = line.splitn(4, '\t');
+            let (Some(letter), Some(x), Some(y), Some(uri)) =
+                (fields.next(), fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let (Some(letter), Ok(x), Ok(y)) = (letter.chars().next(), x.parse(), y.parse()) else {
+                continue;
+            };
+
+            store.insert(
+                letter,
+                Mark {
+                    uri: uri.to_string(),
+                    x,
+                    y,
+                },
+            );
+        }
+
+        store
+    }
+
+    fn save(&self) {
+        let mut contents = String::new();
+        let local = self.local.iter().map(|((_, letter), mark)| (letter, mark));
+        for (letter, mark) in local.chain(self.global.iter()) {
+            contents.push_str(&format!(
+                "{}\t{}\t{}\t{}\n",
+                letter, mark.x, mark.y, mark.uri
+            ));
+        }
+
+        if let Err(err) = paths::write_file(&paths::data_file(MARKS_FILE), &contents) {
+            messages::error(&format!("Failed to save marks: {}", err));
+        }
+    }
+
+    fn insert(&mut self, letter: char, mark: Mark) {
+        if letter.is_ascii_uppercase() {
+            self.global.insert(letter, mark);
+        } else {
+            self.local.insert((page_key(&mark.uri), letter), mark);
+        }
+    }
+
+    fn get(&self, letter: char, current_uri: &str) -> Option<Mark> {
+        if letter.is_ascii_uppercase() {
+            self.global.get(&letter).cloned()
+        } else {
+            self.local.get(&(page_key(current_uri), letter)).cloned()
+        }
+    }
+}
+
+/// Marks ignore the fragment so in-page anchors don't lose them.
+fn page_key(uri: &str) -> String {
+    uri.split('#').next().unwrap_or(uri).to_string()
+}
+
+impl Window {
+    pub(super) fn set_mark(&self, letter: char) {
+        let Some(webview) = self.current_webview() else {
+            return;
+        };
+
+        self.current_position(&webview, move |mark| {
+            with_marks(|marks| {
+                marks.insert(letter, mark);
+                marks.save();
+            })
+        });
+    }
+
+    pub(super) fn jump_to_mark(&self, letter: char) {
+        let Some(webview) = self.current_webview() else {
+            return;
+        };
+        let current_uri = webview.uri().map(|u| u.to_string()).unwrap_or_default();
+
+        let Some(mark) = with_marks(|marks| marks.get(letter, &current_uri)) else {
+            return;
+        };
+
+        self.record_jump(&webview);
+        self.go_to_mark(&mark);
+    }
+
+    /// `''`: return to where we were before the last jump, remembering the
+    /// current position so a second `''` comes back here.
+    pub(super) fn jump_back(&self) {
+        let Some(webview) = self.current_webview() else {
+            return;
+        };
+        let Some(mark) = self.imp().jump_list.borrow_mut().pop() else {
+            return;
+        };
+
+        self.record_jump(&webview);
+        self.go_to_mark(&mark);
+    }
+
+    fn record_jump(&self, webview: &WebView) {
+        self.current_position(
+            webview,
+            glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                move |mark| {
+                    let mut jump_list = window.imp().jump_list.borrow_mut();
+                    if jump_list.len() >= JUMP_LIST_LIMIT {
+                        jump_list.remove(0);
+                    }
+                    jump_list.push(mark);
+                }
+            ),
+        );
+    }
+
+    fn go_to_mark(&self, mark: &Mark) {
+        let key = page_key(&mark.uri);
+
+        if let Some(idx) =
+            self.find_tab(|webview| tab_uri(webview).is_some_and(|uri| page_key(&uri) == key))
+        {
+            self.focus_tab_by_index(idx as i32);
+            if let Some(webview) = self.current_webview() {
+                scroll_to(&webview, mark.x, mark.y);
+            }
+            return;
+        }
+
+        // Not open anywhere: load it and scroll once the page is there.
+        let webview = self.new_tab(&mark.uri);
+        scroll_after_load(&webview, mark.x, mark.y);
+    }
+
+    fn current_position<F: FnOnce(Mark) + 'static>(&self, webview: &WebView, f: F) {
+        let uri = webview.uri().map(|u| u.to_string()).unwrap_or_default();
+
+        webview.evaluate_javascript(
+            "window.scrollX + ',' + window.scrollY",
+            None,
+            None,
+            None::<&gio::Cancellable>,
+            move |result| {
+                let Ok(value) = result else {
+                    return;
+                };
+                let value = value.to_str();
+                let Some((x, y)) = value.split_once(',') else {
+                    return;
+                };
+
+                f(Mark {
+                    uri,
+                    x: x.parse().unwrap_or(0.0),
+                    y: y.parse().unwrap_or(0.0),
+                });
+            },
+        );
+    }
+}
+
+/// Scrolls to `x`, `y` once the page `webview` is loading has finished.
+pub(super) fn scroll_after_load(webview: &WebView, x: f64, y: f64) {
+    let handler = Rc::new(RefCell::new(None));
+    let handler_c = handler.clone();
+    let id = webview.connect_load_changed(move |webview, event| {
+        if event == LoadEvent::Finished {
+            scroll_to(webview, x, y);
+            if let Some(id) = handler_c.take() {
+                webview.disconnect(id);
+            }
+        }
+    });
+    handler.replace(Some(id));
+}
+
+fn scroll_to(webview: &WebView, x: f64, y: f64) {
+    webview.evaluate_javascript(
+        &format!("window.scrollTo({}, {});", x, y),
+        None,
+        None,
+        None::<&gio::Cancellable>,
+        scripts::report_failure(Level::Debug, "Restoring the scroll position"),
+    );
+}
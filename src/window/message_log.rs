@@ -0,0 +1,43 @@
+use glib::subclass::types::ObjectSubclassIsExt as _;
+use gtk4::prelude::EditableExt as _;
+
+use super::{OpenTarget, PaletteAction, Window};
+use crate::messages;
+
+impl Window {
+    /// `:messages` lists the messages of this run in the palette, newest
+    /// first. Typing after it narrows them down.
+    pub(super) fn show_messages(&self, filter: &str) {
+        self.open_command_palette(OpenTarget::NewTab, false);
+
+        let text = format!(":messages {}", filter);
+        let entry = &self.imp().command_entry;
+        entry.set_text(&text);
+        entry.set_position(-1);
+        self.populate_command_palette(&text);
+    }
+
+    /// Palette rows for the messages containing `filter`.
+    pub(super) fn add_message_rows(&self, filter: &str) {
+        let needle = filter.to_lowercase();
+        let found: Vec<_> = messages::all()
+            .into_iter()
+            .rev()
+            .filter(|message| message.text.to_lowercase().contains(&needle))
+            .collect();
+
+        if found.is_empty() {
+            self.add_palette_header("No messages");
+            return;
+        }
+
+        self.add_palette_header("Messages");
+        for message in found {
+            self.add_palette_row(
+                &message.text,
+                &format!("{} {}", message.time_of_day(), message.level),
+                PaletteAction::FillEntry(format!(":messages {}", filter)),
+            );
+        }
+    }
+}
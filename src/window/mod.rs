@@ -1,5 +1,35 @@
+mod adblock;
+mod benchmark;
+mod clipboard;
+mod commands;
+mod crash;
+mod dock;
+mod history;
 mod imp;
+mod load_errors;
+mod marks;
+mod message_log;
+mod navigation;
+mod session;
+mod settings;
+mod site_settings;
+mod stylesheets;
+mod suspend;
+mod tab_bar;
+mod tabs;
+mod userscripts;
+mod workspaces;
+mod zoom;
+
+use std::{
+    rc::Rc,
+    sync::atomic::{AtomicU32, Ordering},
+};
 
+pub use adblock::show_blocked_count;
+use clipboard::Yank;
+use commands::{COMMANDS, command_args, command_name};
+pub use dock::{show_edit_state, show_scroll_percent};
 use glib::{
     GString, Object,
     object::{Cast, ObjectExt},
@@ -8,15 +38,19 @@ use glib::{
 use gtk4::{
     Application, CssProvider, EventControllerKey,
     gdk::{self, ModifierType},
-    gio::{self, prelude::ApplicationExt as _},
-    glib,
+    gio, glib,
     prelude::{
-        BoxExt as _, EditableExt as _, EventControllerExt as _, GtkWindowExt as _,
-        ListBoxRowExt as _, WidgetExt as _,
+        BoxExt as _, EditableExt as _, EventControllerExt as _, GtkApplicationExt as _,
+        GtkWindowExt as _, ListBoxRowExt as _, WidgetExt as _,
     },
 };
-use rand::Rng as _;
-use webkit6::{UserContentManager, UserScript, WebView, prelude::WebViewExt};
+pub use settings::change_setting;
+use suspend::{is_suspended, share_web_process, tab_title, tab_uri};
+use tabs::tab_id;
+pub use userscripts::{handle_gm_message, reply_gm_values};
+use webkit6::{WebView, prelude::WebViewExt};
+
+use crate::{messages::Level, pages, profile::Profile, scripts};
 
 glib::wrapper! {
     pub struct Window(ObjectSubclass<imp::Window>)
@@ -27,18 +61,100 @@ glib::wrapper! {
 
 #[derive(Clone, Debug)]
 enum PaletteAction {
-    SwitchTab(u32),
+    SwitchTab(u32, u32), // Window number, tab index
     OpenUrl(String),
     Search(String),
-    Command(String), // Internal command (quit, reload, etc)
+    Command(String),   // Internal command (quit, reload, etc)
+    FillEntry(String), // Complete the entry text instead of running anything
 }
 
 #[derive(Clone, Debug)]
 struct ActionWrapper(PaletteAction);
 
+/// Where the palette opens URLs and searches.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum OpenTarget {
+    CurrentTab,
+    #[default]
+    NewTab,
+    BackgroundTab,
+}
+
+impl OpenTarget {
+    fn label(self) -> &'static str {
+        match self {
+            OpenTarget::CurrentTab => "Open in current tab",
+            OpenTarget::NewTab => "Open in new tab",
+            OpenTarget::BackgroundTab => "Open in background tab",
+        }
+    }
+}
+
+/// Outcome of feeding the pending key buffer to `run_key_sequence`.
+enum KeySequence {
+    Pending,
+    Done,
+    Unknown,
+}
+
+static NEXT_WINDOW_NUMBER: AtomicU32 = AtomicU32::new(1);
+
 impl Window {
     pub fn new(app: &Application) -> Self {
-        Object::builder().property("application", app).build()
+        let window = Self::new_empty(app);
+        window.open_start_pages();
+        window
+    }
+
+    /// Opens the `start.pages`, the first one in front.
+    fn open_start_pages(&self) {
+        let pages = crate::settings::list("start.pages");
+        let Some((first, rest)) = pages.split_first() else {
+            self.new_tab("about:blank");
+            return;
+        };
+
+        self.new_tab(first);
+        for page in rest {
+            self.add_tab(page, true);
+        }
+    }
+
+    /// A window without any tabs, for tabs moved over from another window.
+    fn new_empty(app: &Application) -> Self {
+        let window: Self = Object::builder().property("application", app).build();
+        window
+            .imp()
+            .number
+            .set(NEXT_WINDOW_NUMBER.fetch_add(1, Ordering::Relaxed));
+        window
+    }
+
+    /// 1-based and never reused, so `:tab-give 2` keeps meaning the same
+    /// window while others come and go.
+    fn number(&self) -> u32 {
+        self.imp().number.get()
+    }
+
+    /// All tilde windows of the application, oldest first.
+    fn app_windows(&self) -> Vec<Window> {
+        let Some(app) = self.application() else {
+            return vec![self.clone()];
+        };
+
+        let mut windows: Vec<Window> = app
+            .windows()
+            .into_iter()
+            .filter_map(|w| w.downcast::<Window>().ok())
+            .collect();
+        windows.sort_by_key(|w| w.number());
+        windows
+    }
+
+    fn window_by_number(&self, number: u32) -> Option<Window> {
+        self.app_windows()
+            .into_iter()
+            .find(|w| w.number() == number)
     }
 
     fn load_css(&self) {
@@ -75,47 +191,102 @@ impl Window {
                             let editable: bool =
                                 unsafe { *webview.data::<bool>("is_editable").unwrap().as_ptr() };
 
-                            if !editable {
+                            // Sites set to pass keys through still open the palette
+                            let passthrough = site_settings::is_passthrough(&webview);
+                            if passthrough && !editable && key == gdk::Key::asciitilde {
+                                window.toggle_command_palette();
+                                return glib::Propagation::Stop;
+                            }
+
+                            if !editable && !passthrough {
+                                if modifier.is_empty()
+                                    && imp.pending_keys.borrow().is_empty()
+                                    && window.handle_error_page_key(&webview, key)
+                                {
+                                    return glib::Propagation::Stop;
+                                }
+
+                                if !modifier.intersects(ModifierType::CONTROL_MASK | ModifierType::ALT_MASK) {
+                                    let pending = !imp.pending_keys.borrow().is_empty();
+                                    if let Some(ch) = key.to_unicode()
+                                        && (pending
+                                            || matches!(ch, 'm' | '\'' | 'y' | 'g' | ']' | '[')
+                                            || window.is_binding_prefix(&ch.to_string()))
+                                    {
+                                        imp.pending_keys.borrow_mut().push(ch);
+                                        let seq = imp.pending_keys.borrow().clone();
+                                        match window.run_key_sequence(&seq) {
+                                            KeySequence::Pending => {}
+                                            KeySequence::Done | KeySequence::Unknown => {
+                                                imp.pending_keys.borrow_mut().clear();
+                                            }
+                                        }
+                                        window.update_keys_label();
+                                        return glib::Propagation::Stop;
+                                    }
+                                }
+
                                 if modifier.is_empty() {
                                     match key {
                                         gdk::Key::f => {
                                             webview.evaluate_javascript(
-                                                "window.__vimium_enter_hint_mode();",
-                                                None,
+                                                &format!(
+                                                    "window.__vimium_enter_hint_mode({{ alphabet: {}, uppercase: {} }});",
+                                                    scripts::js_string(&crate::settings::text("hints.alphabet")),
+                                                    crate::settings::flag("hints.uppercase"),
+                                                ),
+                                                Some(scripts::PAGE_SCRIPT_WORLD),
                                                 None,
                                                 None::<&gio::Cancellable>,
-                                                |_| {},
+                                                scripts::report_failure(Level::Warning, "Hint mode"),
                                             );
                                         },
 
                                         gdk::Key::k => {
                                             webview.evaluate_javascript(
-                                                "document.scrollingElement.scrollBy({ top: -50, behavior: 'smooth' });
-                                                ",
+                                                &format!(
+                                                    "document.scrollingElement.scrollBy({{ top: -{}, behavior: 'smooth' }});",
+                                                    crate::settings::int("scroll.step"),
+                                                ),
                                                 None,
                                                 None,
                                                 None::<&gio::Cancellable>,
-                                                |_| {},
+                                                scripts::report_failure(Level::Warning, "Scrolling"),
                                             );
                                         },
 
                                         gdk::Key::j => {
                                             webview.evaluate_javascript(
-                                                "document.scrollingElement.scrollBy({ top: 50, behavior: 'smooth' });
-                                                ",
+                                                &format!(
+                                                    "document.scrollingElement.scrollBy({{ top: {}, behavior: 'smooth' }});",
+                                                    crate::settings::int("scroll.step"),
+                                                ),
                                                 None,
                                                 None,
                                                 None::<&gio::Cancellable>,
-                                                |_| {},
+                                                scripts::report_failure(Level::Warning, "Scrolling"),
                                             );
                                         },
 
+                                        gdk::Key::o => {
+                                            window.open_command_palette(OpenTarget::CurrentTab, false);
+                                            return glib::Propagation::Stop;
+                                        }
+
+                                        gdk::Key::p => {
+                                            window.paste_and_open(false);
+                                            return glib::Propagation::Stop;
+                                        }
+
                                         gdk::Key::r => {
                                             webview.reload();
                                             return glib::Propagation::Stop;
                                         }
                                         gdk::Key::x => {
-                                            window.close_current_tab();
+                                            // Pinned tabs resist `x`; `:close` still works
+                                            if !window.is_pinned(&webview) {
+                                                window.close_current_tab();
+                                            }
                                             return glib::Propagation::Stop;
                                         }
 
@@ -123,6 +294,20 @@ impl Window {
                                     }
                                 }
 
+                                if modifier == ModifierType::CONTROL_MASK {
+                                    match key {
+                                        gdk::Key::a => {
+                                            window.step_url_number(1);
+                                            return glib::Propagation::Stop;
+                                        }
+                                        gdk::Key::x => {
+                                            window.step_url_number(-1);
+                                            return glib::Propagation::Stop;
+                                        }
+                                        _ => {}
+                                    }
+                                }
+
                                 if modifier.contains(ModifierType::SHIFT_MASK) {
                                     if key == gdk::Key::H  {
                                        if webview.can_go_back() {
@@ -141,12 +326,17 @@ impl Window {
                                     }
 
                                     if key == gdk::Key::Return {
-                                        let mut rng = rand::thread_rng();
-                                        let idx = rng.gen_range(0..2);
-                                        let arr = ["duckduckgo.com", "archlinux.org"];
-                                        println!("{}", format!("{}", arr[idx]));
+                                        window.open_new_tab_page();
+                                        return glib::Propagation::Stop;
+                                    }
 
-                                        window.new_tab(format!("https://{}", arr[idx]).as_str());
+                                    if key == gdk::Key::O {
+                                        window.open_command_palette(OpenTarget::NewTab, false);
+                                        return glib::Propagation::Stop;
+                                    }
+
+                                    if key == gdk::Key::P {
+                                        window.paste_and_open(true);
                                         return glib::Propagation::Stop;
                                     }
 
@@ -160,6 +350,11 @@ impl Window {
                                         return glib::Propagation::Stop;
                                     }
 
+                                    if key == gdk::Key::T {
+                                        window.cycle_tab_bar_mode();
+                                        return glib::Propagation::Stop;
+                                    }
+
                                     if key == gdk::Key::J {
                                         window.cycle_tab(true);
                                         return glib::Propagation::Stop;
@@ -202,39 +397,101 @@ impl Window {
             }
         ));
     }
+
+    fn run_key_sequence(&self, seq: &str) -> KeySequence {
+        if let Some(command) = self.binding_for(seq) {
+            self.run_command(&command);
+            return KeySequence::Done;
+        }
+        if self.is_binding_prefix(seq) {
+            return KeySequence::Pending;
+        }
+
+        let chars: Vec<char> = seq.chars().collect();
+
+        match chars.as_slice() {
+            ['m'] | ['\''] | ['y'] | ['g'] | [']'] | ['['] => KeySequence::Pending,
+            ['\'', '\''] => {
+                self.jump_back();
+                KeySequence::Done
+            }
+            ['m', letter] if letter.is_ascii_alphabetic() => {
+                self.set_mark(*letter);
+                KeySequence::Done
+            }
+            ['\'', letter] if letter.is_ascii_alphabetic() => {
+                self.jump_to_mark(*letter);
+                KeySequence::Done
+            }
+            ['g', 'e'] => {
+                self.open_command_palette(OpenTarget::CurrentTab, true);
+                KeySequence::Done
+            }
+            ['g', 'E'] => {
+                self.open_command_palette(OpenTarget::NewTab, true);
+                KeySequence::Done
+            }
+            ['g', 'u'] => {
+                self.navigate_up(false);
+                KeySequence::Done
+            }
+            ['g', 'U'] => {
+                self.navigate_up(true);
+                KeySequence::Done
+            }
+            [']', ']'] => {
+                self.follow_page_link(true);
+                KeySequence::Done
+            }
+            ['[', '['] => {
+                self.follow_page_link(false);
+                KeySequence::Done
+            }
+            ['g', 'b'] => {
+                self.open_command_palette(OpenTarget::BackgroundTab, false);
+                KeySequence::Done
+            }
+            ['y', kind] => {
+                let what = match kind {
+                    'y' => Yank::Uri,
+                    't' => Yank::Title,
+                    'm' => Yank::Markdown,
+                    'o' => Yank::Org,
+                    _ => return KeySequence::Unknown,
+                };
+                self.yank(what);
+                KeySequence::Done
+            }
+            _ => KeySequence::Unknown,
+        }
+    }
+
     fn execute_palette_action(&self, action: PaletteAction) {
         let imp = self.imp();
 
+        if let PaletteAction::FillEntry(text) = action {
+            imp.command_entry.set_text(&text);
+            imp.command_entry.set_position(-1);
+            return;
+        }
+
         // Hide palette first
         imp.command_palette_container.set_visible(false);
         imp.command_entry.set_text("");
 
+        let target = imp.palette_target.get();
+
         match action {
-            PaletteAction::SwitchTab(idx) => self.focus_tab_by_index(idx as i32),
-            PaletteAction::OpenUrl(url) => self.new_tab(&url),
-            PaletteAction::Search(query) => {
-                let url = format!(
-                    "https://duckduckgo.com/?q={}",
-                    glib::Uri::escape_string(&query, None, true)
-                );
-                self.new_tab(&url);
-            }
-            PaletteAction::Command(cmd) => match cmd.as_str() {
-                "quit" | "q" => {
-                    if let Some(app) = self.application() {
-                        app.quit();
-                    } else {
-                        self.close();
-                    }
-                }
-                "reload" | "r" => {
-                    if let Some(webview) = self.current_webview() {
-                        webview.reload();
-                    }
+            PaletteAction::SwitchTab(number, idx) => {
+                if let Some(window) = self.window_by_number(number) {
+                    window.focus_tab_by_index(idx as i32);
+                    window.present();
                 }
-                "close" | "d" => self.close_current_tab(),
-                _ => println!("Unknown command: {}", cmd),
-            },
+            }
+            PaletteAction::OpenUrl(url) => self.open_in(&url, target),
+            PaletteAction::Search(query) => self.open_in(&search_url(&query), target),
+            PaletteAction::Command(cmd) => self.run_command(&cmd),
+            PaletteAction::FillEntry(_) => {}
         }
     }
 
@@ -250,63 +507,133 @@ impl Window {
         let q_clean = query.trim();
 
         // 1. Check if it's a Command (:)
-        if q_clean.starts_with(":") {
-            let cmd = &q_clean[1..];
+        if let Some(cmd) = q_clean.strip_prefix(':') {
             self.add_palette_row(
                 "Execute Command",
                 &format!("Run: {}", cmd),
                 PaletteAction::Command(cmd.to_string()),
             );
+
+            // Complete command names until the first argument is typed, then
+            // the argument itself where we know the candidates
+            if cmd.contains(char::is_whitespace) {
+                let name = command_name(cmd);
+                let args = command_args(cmd);
+                for (value, description) in self.complete_args(name) {
+                    if value.starts_with(args) && value != args {
+                        self.add_palette_row(
+                            &format!(":{} {}", name, value),
+                            &description,
+                            PaletteAction::Command(format!("{} {}", name, value)),
+                        );
+                    }
+                }
+            } else {
+                let name = command_name(cmd);
+                for info in COMMANDS
+                    .iter()
+                    .filter(|info| info.name.starts_with(name) && info.name != name)
+                {
+                    let action = if info.takes_args() {
+                        PaletteAction::FillEntry(format!(":{} ", info.name))
+                    } else {
+                        PaletteAction::Command(info.name.to_string())
+                    };
+                    self.add_palette_row(
+                        format!(":{} {}", info.name, info.usage).trim_end(),
+                        info.description,
+                        action,
+                    );
+                }
+            }
+
+            if command_name(cmd) == "messages" {
+                self.add_message_rows(command_args(cmd));
+            }
+
+            self.select_first_palette_row();
             return;
         }
 
         // 2. Check if it's a URL or Search
         if !q_clean.is_empty() {
-            if self.is_likely_url(q_clean) {
-                let url = if q_clean.starts_with("http") {
-                    q_clean.to_string()
-                } else {
-                    format!("https://{}", q_clean)
-                };
-                self.add_palette_row("Go to URL", &url.clone(), PaletteAction::OpenUrl(url));
-            } else {
-                self.add_palette_row(
-                    "Search Web",
-                    &format!("DuckDuckGo: {}", q_clean),
-                    PaletteAction::Search(q_clean.to_string()),
-                );
+            match self.classify_query(q_clean) {
+                PaletteAction::OpenUrl(url) => {
+                    self.add_palette_row("Go to URL", &url.clone(), PaletteAction::OpenUrl(url));
+                }
+                action => {
+                    let engine = search_engine_host().unwrap_or_else(|| "Search".to_string());
+                    self.add_palette_row("Search Web", &format!("{}: {}", engine, q_clean), action);
+                }
             }
         }
 
-        // 3. List Open Tabs (filtered)
-        let notebook = &imp.notebook;
-        let n_pages = notebook.n_pages();
-
-        for i in 0..n_pages {
-            if let Some(page) = notebook.nth_page(Some(i)) {
-                if let Ok(webview) = page.downcast::<WebView>() {
-                    let title = webview
-                        .title()
-                        .map(|t| t.to_string())
-                        .unwrap_or("Untitled".into());
-                    let uri = webview.uri().map(|u| u.to_string()).unwrap_or("".into());
-
-                    // Simple fuzzy match
-                    if q_clean.is_empty()
-                        || title.to_lowercase().contains(&q_clean.to_lowercase())
-                        || uri.contains(q_clean)
-                    {
-                        self.add_palette_row(&title, &uri, PaletteAction::SwitchTab(i));
+        // 3. List Open Tabs (filtered), grouped by window when there are several
+        let windows = self.app_windows();
+        for window in &windows {
+            let notebook = &window.imp().notebook;
+            let mut needs_header = windows.len() > 1;
+
+            for i in 0..notebook.n_pages() {
+                if let Some(page) = notebook.nth_page(Some(i)) {
+                    if let Ok(webview) = page.downcast::<WebView>() {
+                        let title = tab_title(&webview).unwrap_or("Untitled".into());
+                        let uri = tab_uri(&webview).unwrap_or("".into());
+
+                        // Simple fuzzy match
+                        if q_clean.is_empty()
+                            || title.to_lowercase().contains(&q_clean.to_lowercase())
+                            || uri.contains(q_clean)
+                        {
+                            if needs_header {
+                                let current = if window == self { " (this window)" } else { "" };
+                                self.add_palette_header(&format!(
+                                    "Window {}{}",
+                                    window.number(),
+                                    current
+                                ));
+                                needs_header = false;
+                            }
+                            let subtitle = if is_suspended(&webview) {
+                                format!("{} (suspended)", uri)
+                            } else {
+                                uri
+                            };
+                            self.add_palette_row(
+                                &title,
+                                &subtitle,
+                                PaletteAction::SwitchTab(window.number(), i),
+                            );
+                        }
                     }
                 }
             }
         }
 
-        if let Some(first_child) = list.first_child() {
-            if let Ok(row) = first_child.downcast::<gtk4::ListBoxRow>() {
+        self.select_first_palette_row();
+    }
+
+    fn select_first_palette_row(&self) {
+        self.select_palette_row_from(0, 1);
+    }
+
+    /// Selects the first selectable row at or after `idx` going in
+    /// direction `step`, skipping group headers. Returns false if there is
+    /// none.
+    fn select_palette_row_from(&self, mut idx: i32, step: i32) -> bool {
+        let list = &self.imp().results_list;
+
+        while idx >= 0 {
+            let Some(row) = list.row_at_index(idx) else {
+                return false;
+            };
+            if row.is_selectable() {
                 list.select_row(Some(&row));
+                return true;
             }
+            idx += step;
         }
+        false
     }
 
     fn setup_palette_controller(&self) {
@@ -352,12 +679,9 @@ impl Window {
                             || key == gdk::Key::Down =>
                     {
                         if let Some(row) = list.selected_row() {
-                            let idx = row.index();
-                            if let Some(next_row) = list.row_at_index(idx + 1) {
-                                list.select_row(Some(&next_row));
-                            }
-                        } else if let Some(row) = list.row_at_index(0) {
-                            list.select_row(Some(&row));
+                            window.select_palette_row_from(row.index() + 1, 1);
+                        } else {
+                            window.select_first_palette_row();
                         }
                         return glib::Propagation::Stop;
                     }
@@ -367,12 +691,7 @@ impl Window {
                             || key == gdk::Key::Up =>
                     {
                         if let Some(row) = list.selected_row() {
-                            let idx = row.index();
-                            if idx > 0 {
-                                if let Some(prev_row) = list.row_at_index(idx - 1) {
-                                    list.select_row(Some(&prev_row));
-                                }
-                            }
+                            window.select_palette_row_from(row.index() - 1, -1);
                         }
                         return glib::Propagation::Stop;
                     }
@@ -391,6 +710,20 @@ impl Window {
         entry.add_controller(controller);
     }
 
+    /// A row that only labels the rows under it.
+    fn add_palette_header(&self, title: &str) {
+        let row = gtk4::ListBoxRow::new();
+        row.set_selectable(false);
+        row.set_activatable(false);
+
+        let label = gtk4::Label::new(Some(title));
+        label.set_xalign(0.0);
+        label.add_css_class("palette-group");
+        row.set_child(Some(&label));
+
+        self.imp().results_list.append(&row);
+    }
+
     fn add_palette_row(&self, title: &str, subtitle: &str, action: PaletteAction) {
         let imp = self.imp();
         let row = gtk4::ListBoxRow::new();
@@ -425,6 +758,33 @@ impl Window {
         query.contains('.') && !query.contains(' ') && !query.starts_with('?')
     }
 
+    /// Decides whether typed (or pasted) text is a URL to open or a web search.
+    fn classify_query(&self, query: &str) -> PaletteAction {
+        if query.starts_with(&format!("{}:", pages::SCHEME)) {
+            PaletteAction::OpenUrl(query.to_string())
+        } else if self.is_likely_url(query) {
+            let url = if query.starts_with("http") {
+                query.to_string()
+            } else {
+                format!("https://{}", query)
+            };
+            PaletteAction::OpenUrl(url)
+        } else {
+            PaletteAction::Search(query.to_string())
+        }
+    }
+
+    fn query_to_url(&self, query: &str) -> String {
+        match self.classify_query(query) {
+            PaletteAction::OpenUrl(url) => url,
+            _ => search_url(query),
+        }
+    }
+
+    fn profile(&self) -> Rc<Profile> {
+        Profile::get(&self.imp().profile.borrow())
+    }
+
     fn current_webview(&self) -> Option<WebView> {
         let imp = self.imp();
         let current_page = imp.notebook.current_page();
@@ -433,20 +793,40 @@ impl Window {
         page.downcast::<WebView>().ok()
     }
 
+    fn find_tab(&self, pred: impl Fn(&WebView) -> bool) -> Option<u32> {
+        let notebook = &self.imp().notebook;
+
+        (0..notebook.n_pages()).find(|&i| {
+            notebook
+                .nth_page(Some(i))
+                .and_then(|page| page.downcast::<WebView>().ok())
+                .is_some_and(|webview| pred(&webview))
+        })
+    }
+
     fn close_current_tab(&self) {
         let imp = self.imp();
         let notebook = &imp.notebook;
 
         if let Some(current_page) = notebook.current_page() {
+            let opener = self.current_webview().and_then(|webview| {
+                self.remember_closed(&webview);
+                let opener = self.tab_opener(&webview);
+                self.forget_tab(&webview);
+                opener
+            });
+
             notebook.remove_page(Some(current_page));
 
             let n_pages = notebook.n_pages();
             if n_pages == 0 {
-                if let Some(app) = self.application() {
-                    app.quit()
-                } else {
+                // The application exits once its last window is gone
+                if !self.leave_empty_workspace() {
                     self.close();
                 }
+            } else if let Some(idx) = opener.and_then(|opener| notebook.page_num(&opener)) {
+                // Closing a child tab goes back to the tab that opened it
+                notebook.set_current_page(Some(idx));
             }
 
             self.update_dock_info();
@@ -454,246 +834,156 @@ impl Window {
         }
     }
 
-    fn toggle_dock(&self) {
+    fn toggle_command_palette(&self) {
         let imp = self.imp();
-        let is_visible = imp.dock_revealer.reveals_child();
 
-        if !is_visible {
-            imp.dock_revealer.set_reveal_child(true);
+        if imp.command_palette_container.is_visible() {
+            imp.command_palette_container.set_visible(false);
         } else {
-            imp.dock_revealer.set_reveal_child(false);
-            self.update_dock_info();
+            self.open_command_palette(OpenTarget::NewTab, false);
         }
     }
 
-    fn toggle_command_palette(&self) {
+    /// Shows the palette for `target`. With `edit_current` the entry starts
+    /// out holding the current page's URL so it can be tweaked.
+    fn open_command_palette(&self, target: OpenTarget, edit_current: bool) {
         let imp = self.imp();
+        imp.palette_target.set(target);
+        imp.palette_header.set_label(target.label());
+
+        let text = if edit_current {
+            self.current_webview()
+                .and_then(|webview| webview.uri())
+                .map(|uri| uri.to_string())
+                .unwrap_or_default()
+        } else {
+            String::new()
+        };
 
-        if imp.command_palette_container.is_visible() {
-            imp.command_palette_container.set_visible(false);
+        imp.command_entry.set_text(&text);
+        self.populate_command_palette(&text);
+        imp.command_palette_container.set_visible(true);
+        imp.command_entry.grab_focus();
+        if edit_current {
+            imp.command_entry.set_position(-1);
         } else {
-            self.populate_command_palette("");
-            imp.command_palette_container.set_visible(true);
-            imp.command_entry.grab_focus();
             imp.command_entry.select_region(0, -1);
         }
     }
 
-    fn new_tab(&self, uri: &str) {
-        let imp = self.imp();
-        let notebook = &imp.notebook;
-        let ucm = UserContentManager::new();
-        let webview: WebView = Object::builder()
-            .property("user-content-manager", &ucm)
-            .build();
-
-        let webview_c = webview.clone();
-
-        ucm.register_script_message_handler("editState", None);
-        ucm.connect_script_message_received(Some("editState"), move |_m, msg| {
-            let is_editable = msg.clone();
-            println!("editable: {}", is_editable);
-            unsafe {
-                webview_c.set_data("is_editable", is_editable);
+    fn open_in(&self, url: &str, target: OpenTarget) {
+        match target {
+            OpenTarget::CurrentTab => match self.current_webview() {
+                Some(webview) => webview.load_uri(url),
+                None => {
+                    self.new_tab(url);
+                }
+            },
+            OpenTarget::NewTab => {
+                self.new_tab(url);
             }
-        });
-
-        let js = r#"
-            function updateEditState() {
-                let el = document.activeElement;
-                let isEditable =
-                    el &&
-                    (
-                        el.isContentEditable ||
-                        el.tagName === "INPUT" ||
-                        el.tagName === "TEXTAREA" ||
-                        el.getAttribute('role') === 'textbox'
-                    );
-                window.webkit.messageHandlers.editState.postMessage(isEditable);
+            OpenTarget::BackgroundTab => {
+                self.add_tab(url, true);
             }
+        }
+    }
 
-            document.addEventListener('focusin', updateEditState);
-            document.addEventListener('focusout', updateEditState);
-            document.addEventListener('selectionchange', updateEditState);
-            updateEditState();
-
-            document.addEventListener("keydown", e => {
-                if (e.key === "Escape") {
-                    const el = document.activeElement;
-                    if (el && (el.tagName === "INPUT" ||
-                               el.tagName === "TEXTAREA" ||
-                               el.isContentEditable ||
-                               el.getAttribute('role') === 'textbox'
-                               )) {
-                        el.blur();
-                        e.preventDefault();
-                    }
-                }
-            });
-
-            (function () {
-                if (window.__vimium_installed) return;
-                window.__vimium_installed = true;
-
-                const HINT_KEYS = "asdfghjklqwertyuiopzxcvbnm";
-                let active = false;
-                let targets = [];
-                let buffer = "";
-                let container = null;
-
-                function encode(n) {
-                    let s = "";
-                    const base = HINT_KEYS.length;
-                    do {
-                        s = HINT_KEYS[n % base] + s;
-                        n = Math.floor(n / base);
-                    } while (n > 0);
-                    return s;
-                }
-
-                function collectTargets() {
-                    const selectors = [
-                        "a[href]",
-                        "button",
-                        "input",
-                        "textarea",
-                        "select",
-                        "[role='button']",
-                        "[onclick]"
-                    ];
-
-                    return Array.from(document.querySelectorAll(selectors.join(",")))
-                        .filter(el => {
-                            const r = el.getBoundingClientRect();
-                            return r.width > 0 && r.height > 0;
-                        });
-                }
-
-                function showHints() {
-                    container = document.createElement("div");
-                    container.id = "__vimium_hints__";
-                    document.body.appendChild(container);
-
-                    targets.forEach((el, i) => {
-                        const r = el.getBoundingClientRect();
-                        const hint = document.createElement("span");
-
-                        hint.textContent = encode(i);
-                        hint.dataset.index = i;
-
-                        Object.assign(hint.style, {
-                            position: "absolute",
-                            left: `${r.left + window.scrollX}px`,
-                            top: `${r.top + window.scrollY}px`,
-                            background: "yellow",
-                            color: "black",
-                            font: "bold 12px monospace",
-                            padding: "1px 3px",
-                            zIndex: 2147483647
-                        });
-
-                        container.appendChild(hint);
-                    });
-                }
-
-                function exit() {
-                    active = false;
-                    buffer = "";
-                    document.removeEventListener("keydown", onKey, true);
-                    container?.remove();
-                    container = null;
-                }
-
-                function onKey(e) {
-                    if (e.key === "Escape") {
-                        exit();
-                        e.preventDefault();
-                        return;
-                    }
-
-                    if (!HINT_KEYS.includes(e.key)) return;
+    fn open_query(&self, query: &str, target: OpenTarget) {
+        if !query.is_empty() {
+            self.open_in(&self.query_to_url(query), target);
+        }
+    }
 
-                    buffer += e.key;
+    fn new_tab(&self, uri: &str) -> WebView {
+        self.add_tab(uri, false)
+    }
 
-                    const matches = Array.from(container.children)
-                        .filter(h => h.textContent.startsWith(buffer));
+    /// Creates a tab for `uri`. Background tabs are added without switching
+    /// to them. The tab has no opener: only tabs a page opens itself record
+    /// the tab they came from.
+    fn add_tab(&self, uri: &str, background: bool) -> WebView {
+        let webview = self.create_webview(None);
+        webview.load_uri(uri);
+        self.insert_tab(&webview, None, background);
 
-                    if (matches.length === 1) {
-                        const idx = +matches[0].dataset.index;
-                        targets[idx].click();
-                        exit();
-                    }
+        webview
+    }
 
-                    e.preventDefault();
-                    e.stopPropagation();
-                }
+    /// Builds a WebView on this window's profile. `related` is the page that
+    /// asked for a new window, which the new view shares a process with.
+    fn create_webview(&self, related: Option<&WebView>) -> WebView {
+        let profile = self.profile();
+        let webview: WebView = Object::builder()
+            .property("web-context", &profile.web_context)
+            .property("network-session", &profile.network_session)
+            .property("user-content-manager", &profile.content_manager)
+            .property_if_some("related-view", related)
+            .build();
 
-                window.__vimium_enter_hint_mode = function () {
-                    if (active) return;
-                    active = true;
-                    buffer = "";
-                    targets = collectTargets();
-                    showHints();
-                    document.addEventListener("keydown", onKey, true);
-                };
-            })();
-        "#;
-
-        let script = UserScript::new(
-            js,
-            webkit6::UserContentInjectedFrames::AllFrames,
-            webkit6::UserScriptInjectionTime::Start,
-            &[],
-            &[],
-        );
-        ucm.add_script(&script);
+        profile.add_view(tab_id(&webview), &webview);
+        if let Some(related) = related {
+            share_web_process(&webview, related);
+        }
+        unsafe { webview.set_data("is_editable", false) }
+        self.track_blocked_count(&webview);
+        self.track_site_settings(&webview);
+        self.track_visits(&webview);
+        self.track_zoom(&webview);
+        self.track_dock_state(&webview);
+        self.track_crashes(&webview);
+        self.track_load_errors(&webview);
 
         webview.set_vexpand(true);
         webview.set_hexpand(true);
 
-        webview.load_uri(uri);
+        // Handlers look the window up from the view rather than capturing
+        // it, so they keep working after the tab moves to another window.
+        webview.connect_notify_local(Some("title"), |webview, _| {
+            if let Some(window) = window_of(webview) {
+                window.update_dock_info();
+                window.refresh_tab_bar();
+            }
+        });
 
-        let page_num = notebook.append_page(&webview, gtk4::Widget::NONE);
-        notebook.set_current_page(Some(page_num));
-        webview.grab_focus();
+        for property in ["favicon", "is-loading", "is-playing-audio"] {
+            webview.connect_notify_local(Some(property), |webview, _| {
+                if let Some(window) = window_of(webview) {
+                    window.refresh_tab_bar();
+                }
+            });
+        }
 
-        self.update_dock_info();
+        webview.connect_notify_local(Some("uri"), |webview, _| {
+            if let Some(window) = window_of(webview) {
+                window.update_dock_info();
+            }
+        });
 
-        webview.connect_notify_local(
-            Some("title"),
-            glib::clone!(
-                #[weak(rename_to = window)]
-                self,
-                move |_webview, _| {
-                    window.update_dock_info();
-                }
-            ),
-        );
+        // target=_blank links and window.open become child tabs
+        webview.connect_create(|webview, _action| {
+            let window = window_of(webview)?;
+            let child = window.create_webview(Some(webview));
+            window.insert_tab(&child, Some(webview), false);
+            Some(child.upcast())
+        });
 
-        webview.connect_notify_local(
-            Some("uri"),
-            glib::clone!(
-                #[weak(rename_to = window)]
-                self,
-                move |_webview, _| {
-                    window.update_dock_info();
-                }
-            ),
-        );
+        webview
     }
 
     fn update_dock_info(&self) {
         let imp = self.imp();
         let notebook = &imp.notebook;
 
-        imp.profile_label.set_label("default profile");
+        imp.profile_label
+            .set_label(&format!("{} profile", self.profile().name));
+        imp.workspace_label.set_label(&imp.workspace.borrow());
 
         if let Some(current_page) = notebook.current_page() {
             if let Some(page_widget) = notebook.nth_page(Some(current_page)) {
                 if let Ok(webview) = page_widget.downcast::<WebView>() {
-                    if let Some(uri) = webview.uri() {
+                    if let Some(uri) = tab_uri(&webview) {
                         imp.uri_label.set_label(&uri);
-                    } else if let Some(title) = webview.title() {
+                    } else if let Some(title) = tab_title(&webview) {
                         imp.uri_label.set_label(&title);
                     } else {
                         imp.uri_label.set_label("Loading...");
@@ -705,13 +995,23 @@ impl Window {
         }
 
         let n_tabs = notebook.n_pages();
-        let tab_text = if n_tabs == 1 {
+        let mut tab_text = if n_tabs == 1 {
             "1 tab open".to_string()
         } else {
             format!("{} tabs open", n_tabs)
         };
 
-        imp.tab_label.set_label(&tab_text);
+        let n_suspended = (0..n_tabs)
+            .filter_map(|idx| self.tab_at(idx))
+            .filter(is_suspended)
+            .count();
+        if n_suspended > 0 {
+            tab_text.push_str(&format!(", {} suspended", n_suspended));
+        }
+
+        self.show_segment("tabs", &tab_text);
+        self.update_dock_segments();
+        self.update_message_label();
     }
 
     fn cycle_tab(&self, forward: bool) {
@@ -748,3 +1048,22 @@ impl Window {
         self.update_dock_info();
     }
 }
+
+/// The `search.engine` URL for `query`.
+fn search_url(query: &str) -> String {
+    crate::settings::text("search.engine")
+        .replace("{}", &glib::Uri::escape_string(query, None, true))
+}
+
+/// The host of the `search.engine` URL, without any `www.`, to name it by.
+fn search_engine_host() -> Option<String> {
+    let url = crate::settings::text("search.engine").replace("{}", "");
+    let uri = glib::Uri::parse(&url, glib::UriFlags::NONE).ok()?;
+    let host = uri.host()?;
+    Some(host.strip_prefix("www.").unwrap_or(&host).to_string())
+}
+
+/// The window a tab currently lives in.
+fn window_of(webview: &WebView) -> Option<Window> {
+    webview.root()?.downcast::<Window>().ok()
+}
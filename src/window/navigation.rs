@@ -0,0 +1,216 @@
+use gtk4::gio;
+use webkit6::prelude::WebViewExt as _;
+
+use super::Window;
+use crate::{messages::Level, scripts};
+
+/// Follows `rel=next`/`rel=prev` if the page declares it, otherwise clicks
+/// the first visible link whose text reads like "next"/"previous".
+const FOLLOW_PAGE_LINK_JS: &str = r#"
+(function (dir) {
+    const rels = dir === "next" ? ["next"] : ["prev", "previous"];
+    for (const rel of rels) {
+        const el = document.querySelector(`link[rel~="${rel}"][href], a[rel~="${rel}"][href]`);
+        if (el) {
+            location.href = el.href;
+            return true;
+        }
+    }
+
+    const pattern = dir === "next"
+        ? /^(next|next page|more)\b|^[›»→>]+$/i
+        : /^(prev|previous|previous page|back)\b|^[‹«←<]+$/i;
+
+    const candidates = document.querySelectorAll("a[href], button, [role='button']");
+    for (const el of candidates) {
+        const r = el.getBoundingClientRect();
+        if (r.width === 0 || r.height === 0) continue;
+
+        const labels = [el.textContent, el.getAttribute("aria-label"), el.getAttribute("title")];
+        if (labels.some(l => l && pattern.test(l.trim()))) {
+            el.click();
+            return true;
+        }
+    }
+    return false;
+})
+"#;
+
+impl Window {
+    /// `gu` drops the query/fragment or the last path segment, `gU` goes to
+    /// the site root.
+    pub(super) fn navigate_up(&self, to_root: bool) {
+        let Some(webview) = self.current_webview() else {
+            return;
+        };
+        let Some(uri) = webview.uri() else {
+            return;
+        };
+
+        let target = if to_root {
+            root_url(&uri)
+        } else {
+            parent_url(&uri)
+        };
+        if let Some(target) = target.filter(|t| t.as_str() != uri.as_str()) {
+            webview.load_uri(&target);
+        }
+    }
+
+    /// `Ctrl+a`/`Ctrl+x`: add `delta` to the last number in the URL.
+    pub(super) fn step_url_number(&self, delta: i64) {
+        let Some(webview) = self.current_webview() else {
+            return;
+        };
+
+        if let Some(target) = webview.uri().and_then(|uri| step_last_number(&uri, delta)) {
+            webview.load_uri(&target);
+        }
+    }
+
+    pub(super) fn follow_page_link(&self, next: bool) {
+        let Some(webview) = self.current_webview() else {
+            return;
+        };
+
+        let dir = if next { "next" } else { "prev" };
+        webview.evaluate_javascript(
+            &format!("{}(\"{}\");", FOLLOW_PAGE_LINK_JS, dir),
+            None,
+            None,
+            None::<&gio::Cancellable>,
+            scripts::report_failure(Level::Warning, "Following the page link"),
+        );
+    }
+}
+
+/// Splits `https://host:port/path?q#f` into the origin and everything after it.
+fn split_origin(uri: &str) -> Option<(&str, &str)> {
+    let start = uri.find("://")? + 3;
+    let end = uri[start..]
+        .find(['/', '?', '#'])
+        .map_or(uri.len(), |i| start + i);
+
+    Some((&uri[..end], &uri[end..]))
+}
+
+fn root_url(uri: &str) -> Option<String> {
+    let (origin, _) = split_origin(uri)?;
+    Some(format!("{}/", origin))
+}
+
+fn parent_url(uri: &str) -> Option<String> {
+    let (origin, rest) = split_origin(uri)?;
+    let path_end = rest.find(['?', '#']).unwrap_or(rest.len());
+    let path = &rest[..path_end];
+
+    // Dropping the query or fragment is the first step up.
+    if path_end < rest.len() {
+        let path = if path.is_empty() { "/" } else { path };
+        return Some(format!("{}{}", origin, path));
+    }
+
+    let trimmed = path.trim_end_matches('/');
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    let parent = &trimmed[..trimmed.rfind('/').map_or(0, |i| i + 1)];
+    let parent = if parent.is_empty() { "/" } else { parent };
+    Some(format!("{}{}", origin, parent))
+}
+
+/// Only numbers after the host are considered, and zero padding is kept so
+/// `page-009` becomes `page-010`.
+fn step_last_number(uri: &str, delta: i64) -> Option<String> {
+    let (origin, rest) = split_origin(uri)?;
+
+    let end = rest.rfind(|c: char| c.is_ascii_digit())? + 1;
+    let start = rest[..end]
+        .char_indices()
+        .rfind(|(_, c)| !c.is_ascii_digit())
+        .map_or(0, |(i, c)| i + c.len_utf8());
+
+    let digits = &rest[start..end];
+    let n: i64 = digits.parse().ok()?;
+    let stepped = n.saturating_add(delta).max(0);
+
+    Some(format!(
+        "{}{}{:0width$}{}",
+        origin,
+        &rest[..start],
+        stepped,
+        &rest[end..],
+        width = digits.len()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn root_keeps_only_the_origin() {
+        assert_eq!(
+            root_url("https://a.com:8080/x/y?q#f").as_deref(),
+            Some("https://a.com:8080/")
+        );
+        assert_eq!(root_url("https://a.com").as_deref(), Some("https://a.com/"));
+        assert_eq!(root_url("about:blank"), None);
+    }
+
+    #[test]
+    fn parent_drops_query_then_path_segments() {
+        assert_eq!(
+            parent_url("https://a.com/x/y?q#f").as_deref(),
+            Some("https://a.com/x/y")
+        );
+        assert_eq!(
+            parent_url("https://a.com?q").as_deref(),
+            Some("https://a.com/")
+        );
+        assert_eq!(
+            parent_url("https://a.com/x/y").as_deref(),
+            Some("https://a.com/x/")
+        );
+        assert_eq!(
+            parent_url("https://a.com/x/y/").as_deref(),
+            Some("https://a.com/x/")
+        );
+        assert_eq!(
+            parent_url("https://a.com/x").as_deref(),
+            Some("https://a.com/")
+        );
+        assert_eq!(parent_url("https://a.com/"), None);
+        assert_eq!(parent_url("https://a.com"), None);
+    }
+
+    #[test]
+    fn step_changes_the_last_number_after_the_host() {
+        assert_eq!(
+            step_last_number("https://a.com/page/2?x=y", 1).as_deref(),
+            Some("https://a.com/page/3?x=y")
+        );
+        assert_eq!(
+            step_last_number("https://a.com/page-009", 1).as_deref(),
+            Some("https://a.com/page-010")
+        );
+        assert_eq!(
+            step_last_number("https://a.com/page/0", -1).as_deref(),
+            Some("https://a.com/page/0")
+        );
+        assert_eq!(step_last_number("https://a2.com/page", 1), None);
+    }
+
+    #[test]
+    fn step_after_multibyte_characters() {
+        assert_eq!(
+            step_last_number("https://a.com/é1", 1).as_deref(),
+            Some("https://a.com/é2")
+        );
+        assert_eq!(
+            step_last_number("https://a.com/日本9", 1).as_deref(),
+            Some("https://a.com/日本10")
+        );
+    }
+}
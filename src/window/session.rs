@@ -0,0 +1,72 @@
+use glib::subclass::types::ObjectSubclassIsExt as _;
+use gtk4::{Application, prelude::GtkWindowExt as _};
+
+use super::Window;
+use crate::session::{Session, WindowState};
+
+impl Window {
+    /// Reopens the windows saved by the last session. Returns nothing when
+    /// there was no session to restore.
+    pub fn restore_session(app: &Application) -> Vec<Window> {
+        Session::load()
+            .windows
+            .into_iter()
+            .filter(|state| !state.is_empty())
+            .map(|state| {
+                let window = Window::new_empty(app);
+                window.restore_window(state);
+                window
+            })
+            .collect()
+    }
+
+    fn restore_window(&self, mut state: WindowState) {
+        let active = state.active.min(state.workspaces.len() - 1);
+        let workspace = state.workspaces.remove(active);
+
+        self.imp().inactive_workspaces.replace(state.workspaces);
+        self.load_workspace(workspace);
+    }
+
+    fn snapshot(&self) -> WindowState {
+        let mut workspaces = vec![self.active_workspace_state()];
+        workspaces.extend(self.imp().inactive_workspaces.borrow().iter().cloned());
+
+        WindowState {
+            workspaces,
+            active: 0,
+        }
+    }
+
+    /// Saves every open window. A window that is closing while others stay
+    /// open is left out, so it does not come back next time.
+    pub(super) fn save_session(&self, closing: bool) {
+        let windows = self.app_windows();
+        let skip_self = closing && windows.len() > 1;
+
+        Session {
+            windows: windows
+                .iter()
+                .filter(|window| !(skip_self && *window == *self))
+                .map(|window| window.snapshot())
+                .collect(),
+        }
+        .save();
+    }
+
+    /// `:window-new [URL]`
+    pub(super) fn open_window(&self, query: &str) {
+        let Some(app) = self.application() else {
+            return;
+        };
+
+        let window = if query.is_empty() {
+            Window::new(&app)
+        } else {
+            let window = Window::new_empty(&app);
+            window.new_tab(&window.query_to_url(query));
+            window
+        };
+        window.present();
+    }
+}
@@ -0,0 +1,108 @@
+use glib::{object::ObjectExt as _, subclass::types::ObjectSubclassIsExt as _};
+use webkit6::{WebView, javascriptcore, prelude::WebViewExt as _};
+
+use super::Window;
+use crate::{messages, pages::SETTINGS_URI, settings};
+
+/// Carries out a change made on the `tilde://settings` page in `webview`:
+/// `set` and `value`, or `reset`. The page reloads to show it once the
+/// setting changed.
+pub fn change_setting(webview: &WebView, msg: &javascriptcore::Value) {
+    if !webview
+        .uri()
+        .is_some_and(|uri| uri.starts_with(SETTINGS_URI))
+    {
+        return;
+    }
+    let prop = |name| {
+        msg.object_get_property(name)
+            .filter(|value| value.is_string())
+            .map(|value| value.to_str().to_string())
+    };
+
+    let result = match (prop("set"), prop("value"), prop("reset")) {
+        (Some(name), Some(value), _) => settings::set(&name, &value),
+        (_, _, Some(name)) => settings::reset(&name),
+        _ => Ok(()),
+    };
+    if let Err(err) = result {
+        messages::error(&err);
+    }
+}
+
+impl Window {
+    /// Reloads open `tilde://settings` pages whenever a setting changes, so
+    /// they show the new value. Pages that are loading already are left
+    /// alone.
+    pub(super) fn watch_settings(&self) {
+        let window = self.downgrade();
+        let id = settings::connect(move |_| {
+            let Some(window) = window.upgrade() else {
+                return;
+            };
+            for webview in window.webviews() {
+                if !webview.is_loading()
+                    && webview
+                        .uri()
+                        .is_some_and(|uri| uri.starts_with(SETTINGS_URI))
+                {
+                    webview.reload();
+                }
+            }
+        });
+        self.imp().settings_listeners.borrow_mut().push(id);
+    }
+
+    /// `:set NAME VALUE`, or `:set -u PATTERN KEY VALUE` for site settings.
+    /// Without a value it shows the current one.
+    pub(super) fn set_command(&self, args: &str) {
+        if let Some(site_args) = args.strip_prefix("-u ") {
+            self.set_site_setting(site_args);
+            return;
+        }
+
+        let (name, value) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        if value.trim().is_empty() {
+            self.get_setting(name);
+            return;
+        }
+
+        if let Err(err) = settings::set(name, value) {
+            messages::error(&err);
+        }
+    }
+
+    pub(super) fn get_setting(&self, name: &str) {
+        match settings::info(name) {
+            Some(info) => messages::info(&format!("{} = {}", info.name, settings::get(info.name))),
+            None => messages::error(&format!("Unknown setting: {}", name)),
+        }
+    }
+
+    pub(super) fn reset_setting(&self, name: &str) {
+        if let Err(err) = settings::reset(name) {
+            messages::error(&err);
+        }
+    }
+
+    pub(super) fn setting_names(&self) -> Vec<(String, String)> {
+        settings::SETTINGS
+            .iter()
+            .map(|info| (info.name.to_string(), info.description.to_string()))
+            .collect()
+    }
+
+    pub(super) fn set_args(&self) -> Vec<(String, String)> {
+        let mut args: Vec<_> = settings::SETTINGS
+            .iter()
+            .map(|info| {
+                (
+                    format!("{} {}", info.name, settings::get(info.name)),
+                    info.description.to_string(),
+                )
+            })
+            .collect();
+        args.extend(self.site_setting_args());
+        args
+    }
+}
@@ -0,0 +1,138 @@
+use glib::object::ObjectExt as _;
+use gtk4::gio;
+use webkit6::{LoadEvent, WebView, prelude::WebViewExt as _};
+
+use super::{Window, commands::split_args, zoom::apply_zoom};
+use crate::{
+    adblock,
+    messages::{self, Level},
+    profile, scripts,
+    site_settings::{self, SiteSettings},
+};
+
+// Inverts the page, then inverts media back so photos look right
+const DARK_MODE_CSS: &str = "html { filter: invert(1) hue-rotate(180deg); background: white; }
+img, video, picture, canvas, iframe, embed, object { filter: invert(1) hue-rotate(180deg); }";
+
+// WebKit's own defaults, for settings no rule overrides
+const DEFAULT_FONT_SIZE: u32 = 16;
+
+/// The site settings applied to the page in `webview`.
+fn applied(webview: &WebView) -> SiteSettings {
+    unsafe {
+        webview
+            .data::<SiteSettings>("site_settings")
+            .map(|settings| settings.as_ref().clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Whether keys should go to the page in `webview` rather than to tilde.
+pub(super) fn is_passthrough(webview: &WebView) -> bool {
+    applied(webview).passthrough == Some(true)
+}
+
+/// Applies the rules matching the page `webview` is loading. Cookies are
+/// left to the content filter, which is per request rather than per view.
+fn apply(webview: &WebView) {
+    let Some(uri) = webview.uri() else {
+        return;
+    };
+    let current = site_settings::for_uri(&uri);
+
+    if let Some(settings) = webview.settings() {
+        // Only the page's own scripts: tilde's script worlds keep running
+        settings.set_enable_javascript_markup(current.javascript.unwrap_or(true));
+        settings.set_auto_load_images(current.images.unwrap_or(true));
+        settings.set_media_playback_requires_user_gesture(!current.autoplay.unwrap_or(true));
+        settings.set_user_agent(current.user_agent.as_deref());
+        settings.set_default_font_size(current.font_size.unwrap_or(DEFAULT_FONT_SIZE));
+    }
+
+    apply_zoom(webview, current.zoom);
+
+    unsafe { webview.set_data("site_settings", current) }
+}
+
+fn apply_dark_mode(webview: &WebView) {
+    if applied(webview).dark_mode != Some(true) {
+        return;
+    }
+
+    webview.evaluate_javascript(
+        &format!(
+            "{{ const sheet = new CSSStyleSheet(); sheet.replaceSync({});
+            document.adoptedStyleSheets = [...document.adoptedStyleSheets, sheet]; }}",
+            scripts::js_string(DARK_MODE_CSS)
+        ),
+        None,
+        None,
+        None::<&gio::Cancellable>,
+        scripts::report_failure(Level::Debug, "Dark mode"),
+    );
+}
+
+impl Window {
+    /// Applies site settings whenever `webview` navigates, before the new
+    /// page is created, and again after redirects.
+    pub(super) fn track_site_settings(&self, webview: &WebView) {
+        webview.connect_load_changed(|webview, event| match event {
+            LoadEvent::Started | LoadEvent::Redirected => apply(webview),
+            LoadEvent::Committed => apply_dark_mode(webview),
+            _ => {}
+        });
+    }
+
+    /// `:set -u PATTERN KEY VALUE` overrides a setting for pages matching
+    /// PATTERN. Open tabs pick it up straight away, the page itself on its
+    /// next load; cookie rules once the content filter is recompiled.
+    /// `args` is what follows `-u`.
+    pub(super) fn set_site_setting(&self, args: &str) {
+        let args = split_args(args);
+        let [pattern, key, value @ ..] = args.as_slice() else {
+            messages::error("Usage: set -u PATTERN KEY VALUE");
+            return;
+        };
+
+        if value.is_empty() {
+            let rule = site_settings::rule(pattern);
+            match rule.entries().into_iter().find(|(name, _)| *name == key) {
+                Some((_, value)) => messages::info(&format!("{} {} = {}", pattern, key, value)),
+                None => messages::info(&format!("{} {} is not set", pattern, key)),
+            }
+            return;
+        }
+
+        if let Err(err) = site_settings::set(pattern, key, &value.join(" ")) {
+            messages::error(&err);
+            return;
+        }
+        if *key == "cookies" {
+            adblock::compile(|filter| profile::install_filter(&filter));
+        }
+
+        for window in self.app_windows() {
+            for webview in window.webviews() {
+                apply(&webview);
+            }
+        }
+    }
+
+    /// Settings of the existing site rules, to edit them.
+    pub(super) fn site_setting_args(&self) -> Vec<(String, String)> {
+        site_settings::patterns()
+            .into_iter()
+            .flat_map(|pattern| {
+                let rule = site_settings::rule(&pattern);
+                site_settings::KEYS.iter().map(move |(key, description)| {
+                    let value = rule
+                        .entries()
+                        .into_iter()
+                        .find(|(name, _)| name == key)
+                        .map_or_else(|| description.to_string(), |(_, value)| value);
+                    (format!("-u '{}' {}", pattern, key), value)
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,31 @@
+use super::Window;
+use crate::{messages, profile, stylesheets};
+
+impl Window {
+    /// `:stylesheet-toggle [NAME]` turns the stylesheet NAME on or off, or
+    /// all user stylesheets when no name is given.
+    pub(super) fn toggle_stylesheet(&self, name: &str) {
+        if stylesheets::toggle(name) {
+            profile::reload_style_sheets();
+        } else {
+            messages::error(&format!("No such stylesheet: {}", name));
+        }
+    }
+
+    /// Completions for `:stylesheet-toggle`.
+    pub(super) fn stylesheet_args(&self) -> Vec<(String, String)> {
+        let all_off = stylesheets::all_disabled();
+
+        stylesheets::load_all()
+            .into_iter()
+            .map(|sheet| {
+                let state = match (all_off, sheet.enabled) {
+                    (true, _) => "off (all stylesheets are off)",
+                    (false, true) => "on",
+                    (false, false) => "off",
+                };
+                (sheet.name, state.to_string())
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,252 @@
+use std::time::{Duration, Instant};
+
+use glib::{
+    object::{Cast as _, ObjectExt as _},
+    subclass::types::ObjectSubclassIsExt as _,
+};
+use gtk4::gio;
+use webkit6::{WebView, WebViewSessionState, prelude::WebViewExt as _};
+
+use super::{Window, marks::scroll_after_load, tabs::tab_id, window_of};
+use crate::{messages, settings};
+
+const IDLE_CHECK_SECONDS: u32 = 60;
+
+/// What is kept of a suspended tab. The page, and the web process behind it
+/// unless other pages share it, are gone until the tab is focused again.
+#[derive(Clone)]
+struct Suspended {
+    uri: String,
+    title: String,
+    scroll: (f64, f64),
+    state: Option<WebViewSessionState>,
+}
+
+fn suspended(webview: &WebView) -> Option<Suspended> {
+    unsafe {
+        webview
+            .data::<Suspended>("suspended")
+            .map(|tab| tab.as_ref().clone())
+    }
+}
+
+pub fn is_suspended(webview: &WebView) -> bool {
+    suspended(webview).is_some()
+}
+
+/// The URI of a tab, whether or not its page is loaded.
+pub fn tab_uri(webview: &WebView) -> Option<String> {
+    suspended(webview)
+        .map(|tab| tab.uri)
+        .or_else(|| webview.uri().map(|u| u.to_string()))
+}
+
+/// The title of a tab, whether or not its page is loaded.
+pub fn tab_title(webview: &WebView) -> Option<String> {
+    suspended(webview)
+        .map(|tab| tab.title)
+        .or_else(|| webview.title().map(|t| t.to_string()))
+        .filter(|t| !t.is_empty())
+}
+
+/// Which web process `webview` runs in. Views opened by a page's
+/// window.open share the process of that page.
+fn web_process(webview: &WebView) -> u64 {
+    unsafe { webview.data::<u64>("web_process").map(|id| *id.as_ptr()) }
+        .unwrap_or_else(|| tab_id(webview))
+}
+
+/// Records that `webview` was created as a related view of `opener`.
+pub fn share_web_process(webview: &WebView, opener: &WebView) {
+    unsafe { webview.set_data("web_process", web_process(opener)) }
+}
+
+fn touch(webview: &WebView) {
+    unsafe { webview.set_data("last_active", Instant::now()) }
+}
+
+fn idle_for(webview: &WebView) -> Option<Duration> {
+    unsafe {
+        webview
+            .data::<Instant>("last_active")
+            .map(|since| since.as_ref().elapsed())
+    }
+}
+
+impl Window {
+    pub(super) fn setup_suspension(&self) {
+        self.imp().notebook.connect_switch_page(glib::clone!(
+            #[weak(rename_to = window)]
+            self,
+            move |_, page, _| {
+                if let Some(webview) = page.downcast_ref::<WebView>() {
+                    touch(webview);
+                    window.resume_tab(webview);
+                }
+            }
+        ));
+
+        glib::timeout_add_seconds_local(
+            IDLE_CHECK_SECONDS,
+            glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                #[upgrade_or]
+                glib::ControlFlow::Break,
+                move || {
+                    window.suspend_idle_tabs();
+                    glib::ControlFlow::Continue
+                }
+            ),
+        );
+    }
+
+    /// Adds a tab for `uri` that only loads once it is focused.
+    pub(super) fn add_lazy_tab(&self, uri: &str, title: &str) -> WebView {
+        let webview = self.create_webview(None);
+        let tab = Suspended {
+            uri: uri.to_string(),
+            title: title.to_string(),
+            scroll: (0.0, 0.0),
+            state: None,
+        };
+        unsafe { webview.set_data("suspended", tab) }
+
+        self.insert_tab(&webview, None, true);
+        webview
+    }
+
+    fn suspend_idle_tabs(&self) {
+        let minutes = settings::int("suspend.after");
+        if minutes <= 0 {
+            return;
+        }
+        let limit = Duration::from_secs(minutes as u64 * 60);
+        let current = self.current_webview();
+
+        for idx in 0..self.imp().notebook.n_pages() {
+            let Some(webview) = self.tab_at(idx) else {
+                continue;
+            };
+            if current.as_ref() == Some(&webview) {
+                touch(&webview);
+                continue;
+            }
+
+            match idle_for(&webview) {
+                None => touch(&webview),
+                Some(idle) if idle >= limit => self.suspend_tab(&webview),
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// `:tab-suspend` suspends every background tab right away.
+    pub(super) fn suspend_background_tabs(&self) {
+        let current = self.current_webview();
+
+        for idx in 0..self.imp().notebook.n_pages() {
+            if let Some(webview) = self.tab_at(idx)
+                && current.as_ref() != Some(&webview)
+            {
+                self.suspend_tab(&webview);
+            }
+        }
+    }
+
+    /// `:suspend-after MINUTES` sets `suspend.after`, where 0 never
+    /// suspends.
+    pub(super) fn set_suspend_after(&self, arg: &str) {
+        if let Err(err) = settings::set("suspend.after", arg.trim()) {
+            messages::error(&err);
+        }
+    }
+
+    /// Discards the page in `webview`, keeping enough to bring it back.
+    /// Pinned tabs and tabs playing audio are left alone.
+    fn suspend_tab(&self, webview: &WebView) {
+        if is_suspended(webview) || self.is_pinned(webview) || webview.is_playing_audio() {
+            return;
+        }
+        let Some(uri) = webview.uri() else {
+            return;
+        };
+
+        let mut tab = Suspended {
+            uri: uri.to_string(),
+            title: tab_title(webview).unwrap_or_default(),
+            scroll: (0.0, 0.0),
+            state: webview.session_state(),
+        };
+
+        let view = webview.clone();
+        webview.evaluate_javascript(
+            "window.scrollX + ',' + window.scrollY",
+            None,
+            None,
+            None::<&gio::Cancellable>,
+            move |result| {
+                let Some(window) = window_of(&view) else {
+                    return;
+                };
+                // Focused again while we were asking for the scroll position
+                if window.current_webview().as_ref() == Some(&view) {
+                    return;
+                }
+
+                if let Ok(value) = result {
+                    let value = value.to_str();
+                    if let Some((x, y)) = value.split_once(',') {
+                        tab.scroll = (x.parse().unwrap_or(0.0), y.parse().unwrap_or(0.0));
+                    }
+                }
+
+                unsafe { view.set_data("suspended", tab) }
+                if window.shares_web_process(&view) {
+                    // Ending the process would take the other pages in it
+                    // down too, so only the page is dropped
+                    view.load_uri("about:blank");
+                } else {
+                    view.terminate_web_process();
+                }
+
+                window.update_dock_info();
+                window.refresh_tab_bar();
+            },
+        );
+    }
+
+    /// Whether a page that is still loaded runs in the same web process as
+    /// `webview`, in any window.
+    fn shares_web_process(&self, webview: &WebView) -> bool {
+        let process = web_process(webview);
+        self.app_windows()
+            .iter()
+            .flat_map(|window| window.webviews())
+            .any(|other| {
+                &other != webview && !is_suspended(&other) && web_process(&other) == process
+            })
+    }
+
+    pub(super) fn resume_tab(&self, webview: &WebView) {
+        let Some(tab) = (unsafe { webview.steal_data::<Suspended>("suspended") }) else {
+            return;
+        };
+
+        let item = tab.state.and_then(|state| {
+            webview.restore_session_state(&state);
+            webview.back_forward_list()?.current_item()
+        });
+        match item {
+            Some(item) => webview.go_to_back_forward_list_item(&item),
+            None => webview.load_uri(&tab.uri),
+        }
+
+        if tab.scroll != (0.0, 0.0) {
+            scroll_after_load(webview, tab.scroll.0, tab.scroll.1);
+        }
+
+        self.update_dock_info();
+        self.refresh_tab_bar();
+    }
+}
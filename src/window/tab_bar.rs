@@ -0,0 +1,176 @@
+use glib::{object::ObjectExt as _, subclass::types::ObjectSubclassIsExt as _};
+use gtk4::{
+    Align, Image, Label, ListBoxRow, Orientation, Spinner, pango,
+    prelude::{BoxExt as _, ListBoxRowExt as _, WidgetExt as _},
+};
+use webkit6::{WebView, prelude::WebViewExt as _};
+
+use super::{
+    Window,
+    suspend::{is_suspended, tab_title, tab_uri},
+};
+
+/// How open tabs are shown. Hidden leaves the palette and the dock count as
+/// the only way to see them, which is how tilde started out.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TabBarMode {
+    #[default]
+    Hidden,
+    Strip,
+    Sidebar,
+}
+
+impl TabBarMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "hidden" => Some(TabBarMode::Hidden),
+            "strip" => Some(TabBarMode::Strip),
+            "sidebar" => Some(TabBarMode::Sidebar),
+            _ => None,
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            TabBarMode::Hidden => TabBarMode::Strip,
+            TabBarMode::Strip => TabBarMode::Sidebar,
+            TabBarMode::Sidebar => TabBarMode::Hidden,
+        }
+    }
+}
+
+// Indentation per level of the opener tree in the sidebar
+const TREE_INDENT: i32 = 14;
+
+impl Window {
+    pub(super) fn setup_tab_bar(&self) {
+        let imp = self.imp();
+        let notebook = &imp.notebook;
+
+        let refresh = glib::clone!(
+            #[weak(rename_to = window)]
+            self,
+            move |_: &gtk4::Notebook, _: &gtk4::Widget, _: u32| window.refresh_tab_bar()
+        );
+        notebook.connect_page_added(refresh.clone());
+        notebook.connect_page_removed(refresh.clone());
+        notebook.connect_page_reordered(refresh);
+
+        notebook.connect_notify_local(
+            Some("page"),
+            glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                move |_, _| window.refresh_tab_bar()
+            ),
+        );
+
+        imp.tab_list.connect_row_activated(glib::clone!(
+            #[weak(rename_to = window)]
+            self,
+            move |_list, row| window.focus_tab_by_index(row.index())
+        ));
+    }
+
+    pub(super) fn cycle_tab_bar_mode(&self) {
+        let mode = self.imp().tab_bar_mode.get().next();
+        self.set_tab_bar_mode(mode);
+    }
+
+    pub(super) fn set_tab_bar_mode(&self, mode: TabBarMode) {
+        let imp = self.imp();
+        imp.tab_bar_mode.set(mode);
+
+        imp.notebook.set_show_tabs(mode == TabBarMode::Strip);
+        imp.tab_sidebar.set_visible(mode == TabBarMode::Sidebar);
+        self.refresh_tab_bar();
+    }
+
+    /// Rebuilds whichever tab bar is visible from the notebook pages.
+    pub(super) fn refresh_tab_bar(&self) {
+        let imp = self.imp();
+
+        match imp.tab_bar_mode.get() {
+            TabBarMode::Hidden => {}
+            TabBarMode::Strip => {
+                for idx in 0..imp.notebook.n_pages() {
+                    if let Some(webview) = self.tab_at(idx) {
+                        let label = Label::new(Some(&display_title(&webview)));
+                        label.set_ellipsize(pango::EllipsizeMode::End);
+                        label.set_max_width_chars(20);
+                        if is_suspended(&webview) {
+                            label.add_css_class("suspended");
+                        }
+                        imp.notebook.set_tab_label(&webview, Some(&label));
+                    }
+                }
+            }
+            TabBarMode::Sidebar => {
+                let list = &imp.tab_list;
+                while let Some(child) = list.first_child() {
+                    list.remove(&child);
+                }
+
+                for idx in 0..imp.notebook.n_pages() {
+                    if let Some(webview) = self.tab_at(idx) {
+                        list.append(&self.sidebar_row(&webview));
+                    }
+                }
+
+                if let Some(current) = imp.notebook.current_page() {
+                    list.select_row(list.row_at_index(current as i32).as_ref());
+                }
+            }
+        }
+    }
+
+    fn sidebar_row(&self, webview: &WebView) -> ListBoxRow {
+        let row_box = gtk4::Box::new(Orientation::Horizontal, 6);
+        row_box.set_margin_start(6 + TREE_INDENT * self.tab_depth(webview) as i32);
+
+        let favicon = match webview.favicon() {
+            Some(texture) => Image::from_paintable(Some(&texture)),
+            None => Image::from_icon_name("web-browser-symbolic"),
+        };
+        favicon.set_pixel_size(16);
+        row_box.append(&favicon);
+
+        let title = Label::new(Some(&display_title(webview)));
+        title.set_ellipsize(pango::EllipsizeMode::End);
+        title.set_hexpand(true);
+        title.set_xalign(0.0);
+        title.add_css_class("tab-title");
+        row_box.append(&title);
+
+        if self.is_pinned(webview) {
+            row_box.append(&Image::from_icon_name("view-pin-symbolic"));
+        }
+
+        if is_suspended(webview) {
+            title.add_css_class("suspended");
+            row_box.append(&Image::from_icon_name("media-playback-pause-symbolic"));
+        }
+
+        if webview.is_loading() {
+            let spinner = Spinner::new();
+            spinner.set_spinning(true);
+            row_box.append(&spinner);
+        }
+
+        if webview.is_playing_audio() {
+            let audio = Image::from_icon_name("audio-volume-high-symbolic");
+            audio.set_valign(Align::Center);
+            row_box.append(&audio);
+        }
+
+        let row = ListBoxRow::new();
+        row.set_child(Some(&row_box));
+        row
+    }
+}
+
+fn display_title(webview: &WebView) -> String {
+    tab_title(webview)
+        .or_else(|| tab_uri(webview))
+        .unwrap_or_else(|| "Untitled".into())
+}
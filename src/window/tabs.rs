@@ -0,0 +1,307 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use glib::{
+    object::{Cast as _, ObjectExt as _},
+    subclass::types::ObjectSubclassIsExt as _,
+};
+use gtk4::prelude::{GtkWindowExt as _, WidgetExt as _};
+use webkit6::{WebView, prelude::WebViewExt as _};
+
+use super::Window;
+use crate::messages;
+
+static NEXT_TAB_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Stable identifier for a tab, unique across the whole application. Page
+/// indices shift as tabs move around, so opener links are kept by id.
+pub fn tab_id(webview: &WebView) -> u64 {
+    unsafe {
+        if let Some(id) = webview.data::<u64>("tab_id") {
+            return *id.as_ptr();
+        }
+
+        let id = NEXT_TAB_ID.fetch_add(1, Ordering::Relaxed);
+        webview.set_data("tab_id", id);
+        id
+    }
+}
+
+impl Window {
+    /// Adds `webview` to the notebook. Tabs with an opener go right after it
+    /// (and after any tabs it already opened) rather than at the end.
+    pub(super) fn insert_tab(&self, webview: &WebView, opener: Option<&WebView>, background: bool) {
+        let imp = self.imp();
+        let notebook = &imp.notebook;
+
+        let position = opener.and_then(|opener| {
+            let opener_id = tab_id(opener);
+            let mut pos = notebook.page_num(opener)? + 1;
+            while let Some(next) = self.tab_at(pos) {
+                if !self.is_descendant_of(&next, opener_id) {
+                    break;
+                }
+                pos += 1;
+            }
+            // Unpinned tabs never go in among the pinned ones
+            Some(pos.max(self.pinned_count()))
+        });
+
+        if let Some(opener) = opener {
+            imp.openers
+                .borrow_mut()
+                .insert(tab_id(webview), tab_id(opener));
+        }
+
+        let page_num = notebook.insert_page(webview, gtk4::Widget::NONE, position);
+        if !background {
+            notebook.set_current_page(Some(page_num));
+            webview.grab_focus();
+        }
+
+        self.update_dock_info();
+    }
+
+    pub(super) fn tab_at(&self, idx: u32) -> Option<WebView> {
+        self.imp()
+            .notebook
+            .nth_page(Some(idx))
+            .and_then(|page| page.downcast::<WebView>().ok())
+    }
+
+    /// Every tab of this window, in order.
+    pub(super) fn webviews(&self) -> Vec<WebView> {
+        (0..self.imp().notebook.n_pages())
+            .filter_map(|idx| self.tab_at(idx))
+            .collect()
+    }
+
+    pub(super) fn tab_opener(&self, webview: &WebView) -> Option<WebView> {
+        let opener_id = *self.imp().openers.borrow().get(&tab_id(webview))?;
+        self.find_tab(|tab| tab_id(tab) == opener_id)
+            .and_then(|idx| self.tab_at(idx))
+    }
+
+    /// How many openers deep `webview` sits in the opener tree.
+    pub(super) fn tab_depth(&self, webview: &WebView) -> usize {
+        let openers = self.imp().openers.borrow();
+        let mut id = tab_id(webview);
+        let mut depth = 0;
+
+        while let Some(&parent) = openers.get(&id) {
+            depth += 1;
+            id = parent;
+        }
+        depth
+    }
+
+    fn is_descendant_of(&self, webview: &WebView, ancestor: u64) -> bool {
+        let openers = self.imp().openers.borrow();
+        let mut id = tab_id(webview);
+
+        while let Some(&parent) = openers.get(&id) {
+            if parent == ancestor {
+                return true;
+            }
+            id = parent;
+        }
+        false
+    }
+
+    /// Drops `webview` from the opener tree. Its children are handed to its
+    /// own opener so the tree stays connected.
+    pub(super) fn forget_tab(&self, webview: &WebView) {
+        let id = tab_id(webview);
+        let mut openers = self.imp().openers.borrow_mut();
+        match openers.remove(&id) {
+            Some(parent) => openers
+                .values_mut()
+                .filter(|opener| **opener == id)
+                .for_each(|opener| *opener = parent),
+            None => openers.retain(|_, opener| *opener != id),
+        }
+    }
+
+    pub(super) fn is_pinned(&self, webview: &WebView) -> bool {
+        self.imp().pinned.borrow().contains(&tab_id(webview))
+    }
+
+    /// Pinned tabs always occupy the first positions.
+    fn pinned_count(&self) -> u32 {
+        let pinned = self.imp().pinned.borrow();
+        (0..self.imp().notebook.n_pages())
+            .filter_map(|idx| self.tab_at(idx))
+            .filter(|webview| pinned.contains(&tab_id(webview)))
+            .count() as u32
+    }
+
+    /// `:tabmove N` moves to position N (1-based), `+N`/`-N` move relative to
+    /// the current position, and a bare `+`/`-` moves by one.
+    pub(super) fn move_current_tab(&self, arg: &str) {
+        let notebook = &self.imp().notebook;
+        let (Some(webview), Some(current)) = (self.current_webview(), notebook.current_page())
+        else {
+            return;
+        };
+
+        let current = current as i64;
+        let target = match arg {
+            "" | "+" => current + 1,
+            "-" => current - 1,
+            _ if arg.starts_with(['+', '-']) => match arg.parse::<i64>() {
+                Ok(delta) => current + delta,
+                Err(_) => return,
+            },
+            _ => match arg.parse::<i64>() {
+                Ok(position) => position - 1,
+                Err(_) => return,
+            },
+        };
+
+        // Pinned and unpinned tabs each stay within their own region
+        let pinned = self.pinned_count() as i64;
+        let (lo, hi) = if self.is_pinned(&webview) {
+            (0, pinned - 1)
+        } else {
+            (pinned, notebook.n_pages() as i64 - 1)
+        };
+
+        notebook.reorder_child(&webview, Some(target.clamp(lo, hi.max(lo)) as u32));
+    }
+
+    pub(super) fn toggle_pin(&self) {
+        let Some(webview) = self.current_webview() else {
+            return;
+        };
+        let id = tab_id(&webview);
+
+        let unpinned = self.imp().pinned.borrow_mut().remove(&id);
+        if unpinned {
+            // First place after the remaining pinned tabs
+            self.imp()
+                .notebook
+                .reorder_child(&webview, Some(self.pinned_count()));
+        } else {
+            // Newly pinned tabs join the end of the pinned group
+            let position = self.pinned_count();
+            self.imp().pinned.borrow_mut().insert(id);
+            self.imp().notebook.reorder_child(&webview, Some(position));
+        }
+
+        self.refresh_tab_bar();
+    }
+
+    /// Opens a copy of the current tab, back/forward history included.
+    pub(super) fn clone_current_tab(&self) {
+        let Some(webview) = self.current_webview() else {
+            return;
+        };
+
+        let clone = self.create_webview(None);
+        if let Some(state) = webview.session_state() {
+            clone.restore_session_state(&state);
+        }
+
+        match clone
+            .back_forward_list()
+            .and_then(|list| list.current_item())
+        {
+            Some(item) => clone.go_to_back_forward_list_item(&item),
+            None => {
+                if let Some(uri) = webview.uri() {
+                    clone.load_uri(&uri);
+                }
+            }
+        }
+
+        self.insert_tab(&clone, Some(&webview), false);
+    }
+
+    /// Closes every unpinned tab other than the current one for which
+    /// `pred(is_left_of_current)` holds.
+    pub(super) fn close_other_tabs(&self, pred: impl Fn(bool) -> bool) {
+        let notebook = &self.imp().notebook;
+        let Some(current) = notebook.current_page() else {
+            return;
+        };
+
+        for idx in (0..notebook.n_pages()).rev() {
+            if idx == current {
+                continue;
+            }
+            if let Some(webview) = self.tab_at(idx)
+                && !self.is_pinned(&webview)
+                && pred(idx < current)
+            {
+                self.remember_closed(&webview);
+                self.detach_tab(&webview);
+            }
+        }
+
+        self.update_dock_info();
+    }
+
+    /// Removes `webview` from this window without destroying it.
+    pub(super) fn detach_tab(&self, webview: &WebView) {
+        self.forget_tab(webview);
+        self.imp().pinned.borrow_mut().remove(&tab_id(webview));
+
+        let notebook = &self.imp().notebook;
+        if let Some(idx) = notebook.page_num(webview) {
+            notebook.remove_page(Some(idx));
+        }
+    }
+
+    /// `:tab-give N` hands the current tab to window N, or to a brand new
+    /// window when no number is given.
+    pub(super) fn give_current_tab(&self, arg: &str) {
+        let Some(webview) = self.current_webview() else {
+            return;
+        };
+
+        let target = if arg.is_empty() {
+            let Some(app) = self.application() else {
+                return;
+            };
+            Window::new_empty(&app)
+        } else {
+            match arg.parse().ok().and_then(|n| self.window_by_number(n)) {
+                Some(window) if window != *self => window,
+                _ => {
+                    messages::error(&format!("No such window: {}", arg));
+                    return;
+                }
+            }
+        };
+
+        self.move_tab_to(&webview, &target);
+    }
+
+    /// `:tab-take W/T` pulls tab T (1-based) out of window W into this one.
+    pub(super) fn take_tab(&self, arg: &str) {
+        let source_and_tab = arg.split_once('/').and_then(|(window, tab)| {
+            let source = self.window_by_number(window.parse().ok()?)?;
+            let tab = source.tab_at(tab.parse::<u32>().ok()?.checked_sub(1)?)?;
+            Some((source, tab))
+        });
+
+        match source_and_tab {
+            Some((source, webview)) if source != *self => source.move_tab_to(&webview, self),
+            _ => messages::error(&format!("No such tab: {}", arg)),
+        }
+    }
+
+    fn move_tab_to(&self, webview: &WebView, target: &Window) {
+        // Keep the view alive while it has no parent
+        let webview = webview.clone();
+        self.detach_tab(&webview);
+
+        target.insert_tab(&webview, None, false);
+        target.present();
+
+        if self.imp().notebook.n_pages() == 0 {
+            self.close();
+        } else {
+            self.update_dock_info();
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use gtk4::prelude::WidgetExt as _;
+use webkit6::{ScriptMessageReply, WebView, javascriptcore, prelude::WebViewExt as _};
+
+use super::{Window, window_of};
+use crate::{messages, profile, userscripts};
+
+impl Window {
+    /// `:userscripts` lists the installed userscripts; `:userscripts ID`
+    /// turns one on or off.
+    pub(super) fn toggle_userscript(&self, id: &str) {
+        let scripts = userscripts::load_all();
+
+        if id.is_empty() {
+            let list: Vec<String> = scripts
+                .iter()
+                .map(|script| {
+                    format!(
+                        "{} [{}] {}",
+                        script.id,
+                        if script.enabled { "on" } else { "off" },
+                        script.name
+                    )
+                })
+                .collect();
+            if list.is_empty() {
+                messages::info("No userscripts installed");
+            } else {
+                messages::info(&list.join(", "));
+            }
+            return;
+        }
+
+        match scripts.iter().find(|script| script.id == id) {
+            Some(script) => {
+                userscripts::set_enabled(id, !script.enabled);
+                profile::reload_scripts();
+            }
+            None => messages::error(&format!("No such userscript: {}", id)),
+        }
+    }
+
+    /// Completions for `:userscripts`.
+    pub(super) fn userscript_args(&self) -> Vec<(String, String)> {
+        userscripts::load_all()
+            .into_iter()
+            .map(|script| {
+                let state = if script.enabled { "on" } else { "off" };
+                let description = if script.description.is_empty() {
+                    format!("{} · {}", state, script.name)
+                } else {
+                    format!("{} · {}: {}", state, script.name, script.description)
+                };
+                (script.id, description)
+            })
+            .collect()
+    }
+}
+
+/// Carries out a `GM_*` call posted by userscript `script`. `webview` is
+/// the tab the script runs in, when known.
+pub fn handle_gm_message(webview: Option<WebView>, script: &str, msg: &javascriptcore::Value) {
+    let Some(op) = string_prop(msg, "op") else {
+        return;
+    };
+
+    match op.as_str() {
+        "setValue" => {
+            if let (Some(key), Some(value)) = (string_prop(msg, "key"), string_prop(msg, "value")) {
+                userscripts::set_value(script, &key, &value);
+            }
+        }
+        "deleteValue" => {
+            if let Some(key) = string_prop(msg, "key") {
+                userscripts::delete_value(script, &key);
+            }
+        }
+        "openInTab" => {
+            let background = msg
+                .object_get_property("background")
+                .is_some_and(|background| background.to_boolean());
+            if let (Some(url), Some(opener)) = (string_prop(msg, "url"), webview)
+                && let Some(window) = window_of(&opener)
+            {
+                let tab = window.create_webview(None);
+                tab.load_uri(&url);
+                window.insert_tab(&tab, Some(&opener), background);
+            }
+        }
+        "setClipboard" => {
+            if let (Some(text), Some(webview)) = (string_prop(msg, "text"), webview) {
+                webview.clipboard().set_text(&text);
+            }
+        }
+        _ => messages::warning(&format!("Unsupported userscript call: {}", op)),
+    }
+}
+
+/// Answers userscript `script` starting up with its stored `GM_setValue`
+/// values.
+pub fn reply_gm_values(script: &str, msg: &javascriptcore::Value, reply: &ScriptMessageReply) {
+    let Some(context) = msg.context() else {
+        return;
+    };
+    let values = userscripts::values_json(script);
+    reply.return_value(&javascriptcore::Value::from_json(&context, &values));
+}
+
+fn string_prop(msg: &javascriptcore::Value, name: &str) -> Option<String> {
+    msg.object_get_property(name)
+        .filter(|value| value.is_string())
+        .map(|value| value.to_str().to_string())
+}
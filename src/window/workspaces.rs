@@ -0,0 +1,112 @@
+use super::{
+    Window,
+    suspend::{tab_title, tab_uri},
+    tabs::tab_id,
+};
+use crate::session::{TabState, WorkspaceState};
+use glib::subclass::types::ObjectSubclassIsExt as _;
+
+impl Window {
+    /// `:workspace NAME` puts the current tabs away and brings up the tabs of
+    /// NAME, creating it if needed. Put-away tabs are kept as URLs, not as
+    /// live WebViews.
+    pub(super) fn switch_workspace(&self, name: &str) {
+        let imp = self.imp();
+        if name.is_empty() || *imp.workspace.borrow() == name {
+            return;
+        }
+
+        let current = self.active_workspace_state();
+        while let Some(webview) = self.tab_at(0) {
+            self.detach_tab(&webview);
+        }
+
+        let target = {
+            let mut inactive = imp.inactive_workspaces.borrow_mut();
+            let target = match inactive.iter().position(|w| w.name == name) {
+                Some(pos) => inactive.remove(pos),
+                None => WorkspaceState {
+                    name: name.to_string(),
+                    ..Default::default()
+                },
+            };
+            inactive.push(current);
+            target
+        };
+
+        self.load_workspace(target);
+    }
+
+    /// Fills the (empty) notebook with the tabs of `workspace`.
+    pub(super) fn load_workspace(&self, workspace: WorkspaceState) {
+        self.imp().workspace.replace(workspace.name);
+
+        for tab in &workspace.tabs {
+            self.restore_tab(tab);
+        }
+        if workspace.tabs.is_empty() {
+            self.open_start_pages();
+        }
+
+        self.focus_tab_by_index(workspace.current as i32);
+        if let Some(webview) = self.current_webview() {
+            self.resume_tab(&webview);
+        }
+    }
+
+    /// Once the last tab of the active workspace is closed, drops that
+    /// workspace and brings up the one put away most recently. Returns
+    /// false when there is no other workspace to go to.
+    pub(super) fn leave_empty_workspace(&self) -> bool {
+        let Some(next) = self.imp().inactive_workspaces.borrow_mut().pop() else {
+            return false;
+        };
+        self.load_workspace(next);
+        true
+    }
+
+    /// Restored tabs load only once they are focused.
+    fn restore_tab(&self, tab: &TabState) {
+        let webview = self.add_lazy_tab(&tab.uri, &tab.title);
+        if tab.pinned {
+            self.imp().pinned.borrow_mut().insert(tab_id(&webview));
+        }
+    }
+
+    pub(super) fn active_workspace_state(&self) -> WorkspaceState {
+        let notebook = &self.imp().notebook;
+
+        let tabs = (0..notebook.n_pages())
+            .filter_map(|idx| self.tab_at(idx))
+            .map(|webview| TabState {
+                uri: tab_uri(&webview).unwrap_or_default(),
+                title: tab_title(&webview).unwrap_or_default(),
+                pinned: self.is_pinned(&webview),
+            })
+            .collect();
+
+        WorkspaceState {
+            name: self.imp().workspace.borrow().clone(),
+            tabs,
+            current: notebook.current_page().unwrap_or(0),
+        }
+    }
+
+    /// Every workspace of this window with its number of tabs, active first.
+    pub(super) fn workspaces(&self) -> Vec<(String, usize)> {
+        let imp = self.imp();
+        let active = (
+            imp.workspace.borrow().clone(),
+            imp.notebook.n_pages() as usize,
+        );
+
+        std::iter::once(active)
+            .chain(
+                imp.inactive_workspaces
+                    .borrow()
+                    .iter()
+                    .map(|w| (w.name.clone(), w.tabs.len())),
+            )
+            .collect()
+    }
+}
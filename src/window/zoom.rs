@@ -0,0 +1,156 @@
+use std::cell::RefCell;
+
+use glib::{object::ObjectExt as _, subclass::types::ObjectSubclassIsExt as _};
+use webkit6::{WebView, prelude::WebViewExt as _};
+
+use super::{Window, adblock::page_host, window_of};
+use crate::{messages, paths, settings, site_settings};
+
+// Zoom levels `+` and `-` step through
+const ZOOM_LEVELS: &[f64] = &[
+    0.3, 0.5, 0.67, 0.8, 0.9, 1.0, 1.1, 1.2, 1.33, 1.5, 1.7, 2.0, 2.4, 3.0,
+];
+const ZOOM_FILE: &str = "zoom";
+
+thread_local! {
+    static REMEMBERED: RefCell<Option<Vec<(String, f64)>>> = const { RefCell::new(None) };
+}
+
+fn with_remembered<T>(f: impl FnOnce(&mut Vec<(String, f64)>) -> T) -> T {
+    REMEMBERED.with_borrow_mut(|levels| f(levels.get_or_insert_with(load)))
+}
+
+/// Zoom levels chosen per host. One per line: <host>\t<level>
+fn load() -> Vec<(String, f64)> {
+    paths::read_list(ZOOM_FILE)
+        .iter()
+        .filter_map(|line| {
+            let (host, level) = line.split_once('\t')?;
+            Some((host.to_string(), level.parse().ok()?))
+        })
+        .collect()
+}
+
+fn remember(host: &str, level: Option<f64>) {
+    let lines: Vec<String> = with_remembered(|levels| {
+        levels.retain(|(h, _)| h != host);
+        levels.extend(level.map(|level| (host.to_string(), level)));
+
+        levels
+            .iter()
+            .map(|(host, level)| format!("{}\t{}", host, level))
+            .collect()
+    });
+    if let Err(err) = paths::write_list(ZOOM_FILE, &lines) {
+        messages::error(&format!("Failed to save zoom levels: {}", err));
+    }
+}
+
+/// Sets the zoom for the page `webview` is loading: the level last chosen
+/// on its host, else `site_zoom` from the site settings, else 100%.
+pub(super) fn apply_zoom(webview: &WebView, site_zoom: Option<f64>) {
+    let host = page_host(webview);
+    let level = host
+        .and_then(|host| {
+            with_remembered(|levels| {
+                levels
+                    .iter()
+                    .find(|(h, _)| *h == host)
+                    .map(|(_, level)| *level)
+            })
+        })
+        .or(site_zoom)
+        .unwrap_or(1.0);
+
+    if webview.zoom_level() != level {
+        webview.set_zoom_level(level);
+    }
+}
+
+fn apply_text_only(webview: &WebView) {
+    if let Some(settings) = webview.settings() {
+        settings.set_zoom_text_only(settings::flag("zoom.text-only"));
+    }
+}
+
+impl Window {
+    /// Keeps the dock's zoom level current and follows `zoom.text-only`.
+    pub(super) fn track_zoom(&self, webview: &WebView) {
+        apply_text_only(webview);
+        webview.connect_notify_local(Some("zoom-level"), |webview, _| {
+            if let Some(window) = window_of(webview) {
+                window.update_zoom_label();
+            }
+        });
+    }
+
+    /// Applies `zoom.text-only` to every tab when it changes.
+    pub(super) fn watch_zoom_setting(&self) {
+        let window = self.downgrade();
+        let id = settings::connect(move |name| {
+            if name != "zoom.text-only" {
+                return;
+            }
+            if let Some(window) = window.upgrade() {
+                window.webviews().iter().for_each(apply_text_only);
+            }
+        });
+        self.imp().settings_listeners.borrow_mut().push(id);
+    }
+
+    pub(super) fn update_zoom_label(&self) {
+        let level = self
+            .current_webview()
+            .map_or(1.0, |webview| webview.zoom_level());
+
+        let text = if level == 1.0 {
+            String::new()
+        } else {
+            format!("zoom {:.0}%", level * 100.0)
+        };
+        self.show_segment("zoom", &text);
+    }
+
+    /// `:zoom +` and `:zoom -` step the zoom level, `:zoom N` sets it to N
+    /// percent and `:zoom` alone resets it. The level is remembered for
+    /// the current host, 100% too when a site rule would zoom it.
+    pub(super) fn zoom(&self, arg: &str) {
+        let Some(webview) = self.current_webview() else {
+            return;
+        };
+        let current = webview.zoom_level();
+
+        let level = match arg {
+            "" => 1.0,
+            "+" => ZOOM_LEVELS
+                .iter()
+                .copied()
+                .find(|&level| level > current + 0.001)
+                .unwrap_or(current),
+            "-" => ZOOM_LEVELS
+                .iter()
+                .copied()
+                .rev()
+                .find(|&level| level < current - 0.001)
+                .unwrap_or(current),
+            _ => match arg.trim_end_matches('%').parse::<f64>() {
+                Ok(percent) if (10.0..=500.0).contains(&percent) => percent / 100.0,
+                _ => {
+                    messages::error("Usage: zoom [+|-|PERCENT]");
+                    return;
+                }
+            },
+        };
+
+        webview.set_zoom_level(level);
+        if let Some(host) = page_host(&webview) {
+            let site_zoom = webview
+                .uri()
+                .and_then(|uri| site_settings::for_uri(&uri).zoom);
+            remember(
+                &host,
+                (level != 1.0 || site_zoom.is_some()).then_some(level),
+            );
+        }
+    }
+}
@@ -0,0 +1,25 @@
+// Estimates how many requests the content blocker stopped, for the dock.
+// WebKit does not report blocked loads, so this counts resources whose load
+// failed without reaching the network, i.e. without a resource timing entry.
+// Other failures that never reach the network, like bad URLs, count too,
+// and blocked requests made from scripts don't.
+
+(function () {
+    if (window !== window.top) return;
+
+    let blocked = 0;
+
+    addEventListener("error", e => {
+        const el = e.target;
+        if (!(el instanceof Element)) return;
+
+        const url = el.currentSrc || el.src || el.href;
+        if (!url || performance.getEntriesByName(url).length) return;
+
+        blocked++;
+        window.webkit.messageHandlers.adblock.postMessage({
+            tab: window.__tildeTab,
+            blocked,
+        });
+    }, true);
+})();
@@ -0,0 +1,51 @@
+// Tells tilde whether the focused element takes text, so typed keys go to
+// the page instead of being read as commands. Only the top frame knows its
+// tab; subframes report as a frame and tilde credits the focused tab. A
+// subframe only claims to be editable while it has focus, so a frame in a
+// background tab can't take keys from the tab in front.
+
+const inSubframe = window !== window.top;
+
+function isEditable(el) {
+    return !!el &&
+        (
+            el.isContentEditable ||
+            el.tagName === "INPUT" ||
+            el.tagName === "TEXTAREA" ||
+            el.getAttribute('role') === 'textbox'
+        );
+}
+
+function updateEditState() {
+    const el = document.activeElement;
+    // A focused frame reports for itself
+    if (el && (el.tagName === "IFRAME" || el.tagName === "FRAME")) return;
+
+    const editable = isEditable(el);
+    if (inSubframe) {
+        if (editable && !document.hasFocus()) return;
+        window.webkit.messageHandlers.editState.postMessage({ frame: true, editable });
+        return;
+    }
+    window.webkit.messageHandlers.editState.postMessage({
+        tab: window.__tildeTab,
+        editable,
+    });
+}
+
+document.addEventListener('focusin', updateEditState);
+document.addEventListener('focusout', updateEditState);
+document.addEventListener('selectionchange', updateEditState);
+// Focus coming back from a subframe doesn't always reach the document
+addEventListener('focus', updateEditState);
+updateEditState();
+
+document.addEventListener("keydown", e => {
+    if (e.key === "Escape") {
+        const el = document.activeElement;
+        if (isEditable(el)) {
+            el.blur();
+            e.preventDefault();
+        }
+    }
+});
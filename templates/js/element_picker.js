@@ -0,0 +1,118 @@
+// Body of the async function `:hide-element` runs in the page, in the world
+// of the page scripts so it can use the hint engine. Labels the elements in
+// view; typing a label highlights that element, ArrowUp and ArrowDown then
+// widen and narrow the pick, Enter picks it and Escape cancels. Resolves to
+// a CSS selector for the picked element, which is hidden straight away, or
+// null. `alphabet` and `uppercase` are the hint settings.
+
+return new Promise(resolve => {
+    const started = window.__vimium_enter_hint_mode({
+        alphabet,
+        uppercase,
+        targets: candidates(),
+        onPick: refine,
+        onCancel: () => resolve(null),
+    });
+    if (!started) resolve(null);
+
+    function refine(picked) {
+        const box = document.createElement("div");
+        Object.assign(box.style, {
+            position: "fixed",
+            pointerEvents: "none",
+            zIndex: 2147483647,
+            background: "rgba(243, 139, 168, 0.25)",
+            outline: "2px solid #f38ba8",
+        });
+        document.documentElement.appendChild(box);
+
+        let target = null;
+        // Elements left behind by widening, to narrow back to
+        const narrower = [];
+
+        function show(el) {
+            target = el;
+            const r = el.getBoundingClientRect();
+            Object.assign(box.style, {
+                left: `${r.left}px`,
+                top: `${r.top}px`,
+                width: `${r.width}px`,
+                height: `${r.height}px`,
+            });
+        }
+
+        function onKey(e) {
+            const parent = target.parentElement;
+            if (e.key === "Escape") {
+                finish(null);
+            } else if (e.key === "Enter") {
+                finish(target);
+            } else if (e.key === "ArrowUp" && parent && parent !== document.documentElement) {
+                narrower.push(target);
+                show(parent);
+            } else if (e.key === "ArrowDown" && narrower.length) {
+                show(narrower.pop());
+            } else {
+                return;
+            }
+            e.preventDefault();
+            e.stopPropagation();
+        }
+
+        function finish(el) {
+            removeEventListener("keydown", onKey, true);
+            box.remove();
+
+            const selector = el && selectorFor(el);
+            if (selector) el.style.setProperty("display", "none", "important");
+            resolve(selector || null);
+        }
+
+        show(picked);
+        addEventListener("keydown", onKey, true);
+    }
+});
+
+// Elements in view worth labelling: big enough to be seen, and not just a
+// wrapper the same size as the element around it.
+function candidates() {
+    const visible = r =>
+        r.width >= 16 && r.height >= 16 &&
+        r.bottom > 0 && r.right > 0 &&
+        r.top < innerHeight && r.left < innerWidth;
+
+    return Array.from(document.body.querySelectorAll("*")).filter(el => {
+        const r = el.getBoundingClientRect();
+        if (!visible(r)) return false;
+
+        const p = el.parentElement.getBoundingClientRect();
+        return r.left !== p.left || r.top !== p.top ||
+            r.width !== p.width || r.height !== p.height;
+    });
+}
+
+// The shortest chain of tag, classes and position from the nearest element
+// with an id that picks out `el` alone.
+function selectorFor(el) {
+    const parts = [];
+
+    for (let node = el; node && node !== document.body; node = node.parentElement) {
+        if (node === document.documentElement) return null;
+        if (node.id) {
+            parts.unshift("#" + CSS.escape(node.id));
+            break;
+        }
+
+        let part = node.localName;
+        for (const name of node.classList) part += "." + CSS.escape(name);
+
+        const siblings = Array.from(node.parentElement?.children || [])
+            .filter(sibling => sibling.localName === node.localName);
+        if (siblings.length > 1) part += `:nth-of-type(${siblings.indexOf(node) + 1})`;
+
+        parts.unshift(part);
+        if (document.querySelectorAll(parts.join(" > ")).length === 1) break;
+    }
+
+    return parts.join(" > ") || null;
+}
@@ -0,0 +1,76 @@
+// The GM_* API offered to userscripts. Each userscript is wrapped in a
+// function that calls startGM with its info, its @grant list and the
+// message handlers tilde registered for that script alone, so every script
+// gets its own copy. Only granted calls are defined.
+
+// Runs `run` with the script's GM API once its stored values are known.
+// Scripts that run at document-start can't wait for tilde to answer, so
+// they pass `snapshot`, the values stored when the scripts were last
+// installed, and start straight away. The values are brought up to date
+// once tilde answers, keeping whatever the script wrote in the meantime.
+function startGM(info, grants, handlers, snapshot, run) {
+    const asked = handlers.values.postMessage({});
+    if (!snapshot) {
+        asked.then(values => run(makeGM(info, values, grants, handlers.gm, new Set())));
+        return;
+    }
+
+    const values = Object.assign({}, snapshot);
+    const written = new Set();
+    asked.then(stored => {
+        for (const key of Object.keys(values)) {
+            if (!written.has(key)) delete values[key];
+        }
+        for (const [key, value] of Object.entries(stored)) {
+            if (!written.has(key)) values[key] = value;
+        }
+    });
+    run(makeGM(info, values, grants, handlers.gm, written));
+}
+
+// `written` collects the keys the script sets or deletes.
+function makeGM(info, values, grants, handler, written) {
+    const granted = name =>
+        grants.includes(name) || grants.includes("GM." + name.slice(3));
+
+    function post(op, args) {
+        handler.postMessage(Object.assign({ tab: window.__tildeTab, op }, args));
+    }
+
+    const api = {
+        GM_getValue: (key, fallback) => key in values ? values[key] : fallback,
+        GM_setValue: (key, value) => {
+            values[key] = value;
+            written.add(String(key));
+            post("setValue", { key: String(key), value: JSON.stringify(value) });
+        },
+        GM_deleteValue: key => {
+            delete values[key];
+            written.add(String(key));
+            post("deleteValue", { key: String(key) });
+        },
+        GM_listValues: () => Object.keys(values),
+        GM_log: (...args) => console.log(`[${info.script.name}]`, ...args),
+        GM_addStyle: css => {
+            const style = document.createElement("style");
+            style.textContent = css;
+            (document.head || document.documentElement).appendChild(style);
+            return style;
+        },
+        GM_openInTab: (url, options) => {
+            const background = typeof options === "object"
+                ? options.active === false
+                : !!options;
+            post("openInTab", { url: new URL(url, location.href).href, background });
+        },
+        GM_setClipboard: text => post("setClipboard", { text: String(text) }),
+    };
+
+    const gm = { GM_info: info, GM: { info } };
+    for (const [name, fn] of Object.entries(api)) {
+        if (!granted(name)) continue;
+        gm[name] = fn;
+        gm.GM[name.slice(3)] = async (...args) => fn(...args);
+    }
+    return gm;
+}
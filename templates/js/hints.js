@@ -0,0 +1,126 @@
+// Link hints: labels every clickable element and clicks the one whose label
+// is typed. Other modes, like the element picker, pass their own targets and
+// what to do with the picked one.
+
+(function () {
+    if (window.__vimium_installed) return;
+    window.__vimium_installed = true;
+
+    // Replaced by the `hints.alphabet` setting when hint mode starts
+    let hintKeys = "asdfghjklqwertyuiopzxcvbnm";
+    let uppercase = false;
+    let active = false;
+    let targets = [];
+    let buffer = "";
+    let container = null;
+    let onPick = null;
+    let onCancel = null;
+
+    function encode(n) {
+        let s = "";
+        const base = hintKeys.length;
+        do {
+            s = hintKeys[n % base] + s;
+            n = Math.floor(n / base);
+        } while (n > 0);
+        return s;
+    }
+
+    function collectTargets() {
+        const selectors = [
+            "a[href]",
+            "button",
+            "input",
+            "textarea",
+            "select",
+            "[role='button']",
+            "[onclick]"
+        ];
+
+        return Array.from(document.querySelectorAll(selectors.join(",")))
+            .filter(el => {
+                const r = el.getBoundingClientRect();
+                return r.width > 0 && r.height > 0;
+            });
+    }
+
+    function showHints() {
+        container = document.createElement("div");
+        container.id = "__vimium_hints__";
+        document.body.appendChild(container);
+
+        targets.forEach((el, i) => {
+            const r = el.getBoundingClientRect();
+            const hint = document.createElement("span");
+
+            hint.textContent = encode(i);
+            hint.dataset.index = i;
+
+            Object.assign(hint.style, {
+                position: "absolute",
+                left: `${r.left + window.scrollX}px`,
+                top: `${r.top + window.scrollY}px`,
+                background: "yellow",
+                color: "black",
+                font: "bold 12px monospace",
+                padding: "1px 3px",
+                textTransform: uppercase ? "uppercase" : "none",
+                zIndex: 2147483647
+            });
+
+            container.appendChild(hint);
+        });
+    }
+
+    function exit() {
+        active = false;
+        onPick = onCancel = null;
+        buffer = "";
+        document.removeEventListener("keydown", onKey, true);
+        container?.remove();
+        container = null;
+    }
+
+    function onKey(e) {
+        if (e.key === "Escape") {
+            const cancel = onCancel;
+            exit();
+            cancel?.();
+            e.preventDefault();
+            return;
+        }
+
+        if (!hintKeys.includes(e.key)) return;
+
+        buffer += e.key;
+
+        const matches = Array.from(container.children)
+            .filter(h => h.textContent.startsWith(buffer));
+
+        if (matches.length === 1) {
+            const target = targets[+matches[0].dataset.index];
+            const pick = onPick || (el => el.click());
+            exit();
+            pick(target);
+        }
+
+        e.preventDefault();
+        e.stopPropagation();
+    }
+
+    // Returns whether hint mode started, which it doesn't while it is
+    // already on
+    window.__vimium_enter_hint_mode = function (options = {}) {
+        if (active) return false;
+        active = true;
+        hintKeys = options.alphabet || hintKeys;
+        uppercase = !!options.uppercase;
+        onPick = options.onPick || null;
+        onCancel = options.onCancel || null;
+        buffer = "";
+        targets = options.targets || collectTargets();
+        showHints();
+        document.addEventListener("keydown", onKey, true);
+        return true;
+    };
+})();
@@ -0,0 +1,36 @@
+// Reports how far down the page is scrolled, for the dock. Pages that don't
+// scroll report -1.
+
+(function () {
+    if (window !== window.top) return;
+
+    let last = null;
+    let queued = false;
+
+    function report() {
+        queued = false;
+        const el = document.scrollingElement;
+        if (!el) return;
+
+        const max = el.scrollHeight - el.clientHeight;
+        const percent = max > 0 ? Math.round(el.scrollTop / max * 100) : -1;
+        if (percent === last) return;
+
+        last = percent;
+        window.webkit.messageHandlers.scroll.postMessage({
+            tab: window.__tildeTab,
+            percent,
+        });
+    }
+
+    // At most one report per frame
+    function schedule() {
+        if (queued) return;
+        queued = true;
+        requestAnimationFrame(report);
+    }
+
+    addEventListener("scroll", schedule, { passive: true });
+    addEventListener("resize", schedule);
+    addEventListener("load", schedule);
+})();
@@ -0,0 +1,23 @@
+// tilde://settings: sends each change to tilde, which carries it out and
+// reloads the page. Runs in tilde's own script world, so only this page can
+// change settings, and the change never ends up in the page's URL.
+
+(function () {
+    if (window !== window.top || !location.href.startsWith("tilde://settings")) return;
+
+    function send(change) {
+        window.webkit.messageHandlers.settings.postMessage(
+            Object.assign({ tab: window.__tildeTab }, change));
+    }
+
+    for (const input of document.querySelectorAll("[data-setting]")) {
+        input.addEventListener("change", () => {
+            const value = input.type === "checkbox" ? String(input.checked) : input.value;
+            send({ set: input.dataset.setting, value });
+        });
+    }
+
+    for (const button of document.querySelectorAll("[data-reset]")) {
+        button.addEventListener("click", () => send({ reset: button.dataset.reset }));
+    }
+})();
@@ -0,0 +1,47 @@
+// Minimal harness for the page scripts in templates/js. Fixture pages load
+// this first, then the scripts under test, then call `test` for each case.
+// Open a fixture in any browser (or in tilde): the title ends up as PASS or
+// FAIL and the details are listed in the page.
+
+window.__messages = [];
+
+if (!window.webkit) {
+    window.webkit = {
+        messageHandlers: {
+            editState: {
+                postMessage: msg => window.__messages.push(msg),
+            },
+        },
+    };
+}
+
+const results = [];
+
+function assert(cond, message) {
+    if (!cond) throw new Error(message || "assertion failed");
+}
+
+function key(k) {
+    document.dispatchEvent(new KeyboardEvent("keydown", { key: k, bubbles: true }));
+}
+
+function test(name, fn) {
+    try {
+        fn();
+        results.push(`ok   ${name}`);
+    } catch (e) {
+        results.push(`FAIL ${name}: ${e.message}`);
+    }
+}
+
+window.addEventListener("load", () => {
+    setTimeout(() => {
+        const failed = results.filter(r => r.startsWith("FAIL")).length;
+        document.title = failed ? `FAIL ${failed}` : "PASS";
+
+        const pre = document.createElement("pre");
+        pre.id = "results";
+        pre.textContent = results.join("\n");
+        document.body.appendChild(pre);
+    });
+});